@@ -1,6 +1,9 @@
+use std::rc::Rc;
+
+use crate::span::Span;
 use crate::value::Value;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Program {
     pub globals: Vec<Global>,
 }
@@ -8,6 +11,10 @@ pub struct Program {
 #[derive(Debug)]
 pub enum Global {
     Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Variable(GlobalVariable),
+    Import(Import),
 }
 
 #[derive(Debug)]
@@ -15,73 +22,415 @@ pub struct Function {
     pub name: String,
     pub parameters: Vec<String>,
     pub block: Block,
+    /// Span of the signature, from `fn` to the closing parenthesis.
+    pub span: Span,
+    /// The number of local slots a call needs: the parameters first, then
+    /// captured variables and other locals. Set by the resolver.
+    pub slots: usize,
+    /// The variables a lambda captures when it is created. Set by the
+    /// resolver.
+    pub captures: Vec<Capture>,
+}
+
+/// A variable a lambda captures from the function creating it.
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    /// The slot of the variable in the creating function.
+    pub from: usize,
+    /// The slot it is copied to when the lambda is called.
+    pub to: usize,
+}
+
+/// A struct definition, `struct Point { x, y }`.
+#[derive(Debug)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub span: Span,
+}
+
+/// An enum definition, `enum Shape { Circle(r), Rect(w, h), Empty }`.
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+/// `import "math.salt";` or `import math;`. The definitions of the imported
+/// file are then available qualified with `name`, as in `math.gcd`.
+#[derive(Debug)]
+pub struct Import {
+    /// Path of the imported file, relative to the importing file.
+    pub path: String,
+    pub name: String,
+    pub span: Span,
+}
+
+/// A top-level variable, `const LIMIT = 10;` or `global count = 0;`.
+/// Constants cannot be assigned to.
+#[derive(Debug)]
+pub struct GlobalVariable {
+    pub name: String,
+    pub expr: Expr,
+    pub constant: bool,
+    pub span: Span,
+    /// The number of local slots the initializer needs, for the bindings of
+    /// `match` expressions. Set by the resolver.
+    pub slots: usize,
+}
+
+#[derive(Debug)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub enum Statement {
+    Let(Let),
     If(IfStmt),
     While(WhileLoop),
+    For(ForLoop),
     Return(Return),
     Expr(Expr),
     Assignment(Assignment),
     Print(Print),
+    Break(Break),
+    Continue(Continue),
+    Match(Match<Block>),
+}
+
+impl Statement {
+    // No diagnostic covers a whole statement yet.
+    #[allow(dead_code)]
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(let_stmt) => let_stmt.span,
+            Statement::If(if_stmt) => if_stmt.span,
+            Statement::While(while_loop) => while_loop.span,
+            Statement::For(for_loop) => for_loop.span,
+            Statement::Return(return_stmt) => return_stmt.span,
+            Statement::Expr(expr) => expr.span(),
+            Statement::Assignment(assignment) => assignment.span,
+            Statement::Print(print) => print.span,
+            Statement::Break(break_stmt) => break_stmt.span,
+            Statement::Continue(continue_stmt) => continue_stmt.span,
+            Statement::Match(match_stmt) => match_stmt.span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Let {
+    pub name: String,
+    pub expr: Expr,
+    pub span: Span,
+    /// Set by the resolver.
+    pub slot: usize,
 }
 
 #[derive(Debug)]
 pub struct IfStmt {
     pub condition: Expr,
     pub body: Block,
+    pub else_branch: Option<Else>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Else {
+    Block(Block),
+    If(Box<IfStmt>),
 }
 
 #[derive(Debug)]
 pub struct WhileLoop {
     pub condition: Expr,
     pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct ForLoop {
+    pub variable: String,
+    pub iterable: Iterable,
+    pub body: Block,
+    pub span: Span,
+    /// The slot of `variable`. Set by the resolver.
+    pub slot: usize,
+}
+
+/// What a `for` loop iterates over: a range of integers, or the elements of
+/// an array or keys of a map produced by an expression.
+#[derive(Debug)]
+pub enum Iterable {
+    Range(Box<Range>),
+    Expr(Expr),
+}
+
+/// `start..end`, `start..=end`, optionally followed by `step <expr>`.
+#[derive(Debug)]
+pub struct Range {
+    pub start: Expr,
+    pub end: Expr,
+    pub step: Option<Expr>,
+    pub inclusive: bool,
+}
+
+/// `match scrutinee { pattern => body, ... }`. As a statement each arm's
+/// body is a block; as an expression it is an expression.
+#[derive(Debug)]
+pub struct Match<T> {
+    pub scrutinee: Box<Expr>,
+    pub arms: Vec<Arm<T>>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Arm<T> {
+    pub pattern: Pattern,
+    pub body: T,
+    #[allow(dead_code)]
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard(Span),
+    /// A name, which matches anything and binds it.
+    Binding(Name),
+    Literal(Literal),
+    Variant(VariantPattern),
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span) => *span,
+            Pattern::Binding(name) => name.span,
+            Pattern::Literal(literal) => literal.span,
+            Pattern::Variant(variant) => variant.span,
+        }
+    }
+}
+
+/// `Shape::Rect(w, _)`. The parentheses are omitted for variants without
+/// fields.
+#[derive(Debug)]
+pub struct VariantPattern {
+    /// The import qualifying the enum, as in `shapes.Shape::Rect(w, _)`.
+    /// Without one, the enum is one of the current module.
+    pub module: Option<Name>,
+    pub enum_name: String,
+    pub variant: Name,
+    pub fields: Vec<Pattern>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Block {
     pub statements: Vec<Statement>,
+    #[allow(dead_code)]
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Return {
     pub expr: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Break {
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Continue {
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Assignment {
-    pub name: String,
+    pub target: Target,
     pub expr: Expr,
+    pub span: Span,
+}
+
+/// The place an assignment writes to.
+#[derive(Debug)]
+pub enum Target {
+    Name(Name),
+    Index(Index),
+    Field(Field),
 }
 
 #[derive(Debug)]
 pub struct Print {
     pub expr: Expr,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub enum Expr {
-    Literal(Value),
-    Name(String),
+    Literal(Literal),
+    Name(Name),
     Call(Call),
+    Lambda(Lambda),
+    Array(Array),
+    Map(MapLiteral),
+    StructLiteral(StructLiteral),
+    EnumLiteral(Box<EnumLiteral>),
+    Index(Index),
+    Field(Field),
 
     UnaryOp(UnaryOp),
     BinaryOp(BinaryOp),
 
     Time(Time),
+    Match(Match<Expr>),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal(literal) => literal.span,
+            Expr::Name(name) => name.span,
+            Expr::Call(call) => call.span,
+            Expr::Lambda(lambda) => lambda.span,
+            Expr::Array(array) => array.span,
+            Expr::Map(map) => map.span,
+            Expr::StructLiteral(literal) => literal.span,
+            Expr::EnumLiteral(literal) => literal.span,
+            Expr::Index(index) => index.span,
+            Expr::Field(field) => field.span,
+            Expr::UnaryOp(unary_op) => unary_op.span,
+            Expr::BinaryOp(binary_op) => binary_op.span,
+            Expr::Time(time) => time.span,
+            Expr::Match(match_expr) => match_expr.span,
+        }
+    }
 }
 
+#[derive(Debug)]
+pub struct Literal {
+    pub value: Value,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Name {
+    pub name: String,
+    pub span: Span,
+    pub resolution: Resolution,
+}
+
+impl Name {
+    pub fn new(name: String, span: Span) -> Self {
+        Self {
+            name,
+            span,
+            resolution: Resolution::Unresolved,
+        }
+    }
+
+    /// The slot of a name the resolver found to be a local variable.
+    pub fn slot(&self) -> Option<usize> {
+        match self.resolution {
+            Resolution::Local(slot) => Some(slot),
+            Resolution::Unresolved | Resolution::Global | Resolution::Import => None,
+        }
+    }
+}
+
+/// What a variable name refers to, as determined by the resolver. Other
+/// names, such as those of fields, stay unresolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Unresolved,
+    /// A local variable, parameter or captured variable, by slot.
+    Local(usize),
+    /// A global variable, function or builtin, looked up by name in the
+    /// module.
+    Global,
+    /// An imported module, before a `.`.
+    Import,
+}
+
+/// Calls the value of `callee`, which is usually the name of a function.
 #[derive(Debug)]
 pub struct Call {
+    pub callee: Box<Expr>,
+    pub arguments: Vec<Expr>,
+    pub span: Span,
+}
+
+/// An anonymous function, `fn(x) { return x * 2; }`.
+#[derive(Debug)]
+pub struct Lambda {
+    pub function: Rc<Function>,
+    pub span: Span,
+}
+
+/// An array literal, `[a, b, c]`.
+#[derive(Debug)]
+pub struct Array {
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
+/// A map literal, `{ key: value, ... }`.
+#[derive(Debug)]
+pub struct MapLiteral {
+    pub entries: Vec<(Expr, Expr)>,
+    pub span: Span,
+}
+
+/// Constructs a struct, `Point { x: 1, y: 2 }`.
+#[derive(Debug)]
+pub struct StructLiteral {
+    /// The import qualifying the struct, like `VariantPattern::module`.
+    pub module: Option<Name>,
     pub name: String,
+    pub fields: Vec<(Name, Expr)>,
+    pub span: Span,
+}
+
+/// Constructs an enum value, `Shape::Circle(1)` or `Shape::Empty`.
+#[derive(Debug)]
+pub struct EnumLiteral {
+    /// The import qualifying the enum, like `VariantPattern::module`.
+    pub module: Option<Name>,
+    pub enum_name: String,
+    pub variant: Name,
     pub arguments: Vec<Expr>,
+    pub span: Span,
+}
+
+/// `target[index]`.
+#[derive(Debug)]
+pub struct Index {
+    pub target: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+
+/// `target.field`.
+#[derive(Debug)]
+pub struct Field {
+    pub target: Box<Expr>,
+    pub field: Name,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct UnaryOp {
     pub op_type: UnaryOpType,
     pub expr: Box<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -89,17 +438,21 @@ pub struct BinaryOp {
     pub op_type: BinaryOpType,
     pub left: Box<Expr>,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
-pub struct Time {}
+pub struct Time {
+    pub span: Span,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOpType {
     Negate,
+    Not,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOpType {
     Addition,
     Subtraction,
@@ -113,4 +466,7 @@ pub enum BinaryOpType {
     LessThanOrEqualTo,
     GreaterThan,
     GreaterThanOrEqualTo,
+
+    And,
+    Or,
 }
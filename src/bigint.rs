@@ -0,0 +1,364 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+const BASE_BITS: u32 = 32;
+/// The largest power of ten that fits in a limb, used for decimal conversion.
+const DECIMAL_BASE: u32 = 1_000_000_000;
+const DECIMAL_DIGITS: usize = 9;
+
+/// An arbitrary-precision signed integer, stored as a sign and a magnitude of
+/// little-endian base 2^32 limbs. The magnitude never has trailing zero limbs
+/// and zero is never negative, so derived equality is structural.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        trim(&mut magnitude);
+        let negative = negative && !magnitude.is_empty();
+        Self {
+            negative,
+            magnitude,
+        }
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        let abs = value.unsigned_abs();
+        Self::new(value < 0, vec![abs as u32, (abs >> BASE_BITS) as u32])
+    }
+
+    /// Parses a non-empty string of decimal digits.
+    pub fn parse(digits: &str) -> Option<Self> {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        let first_chunk = match digits.len() % DECIMAL_DIGITS {
+            0 => DECIMAL_DIGITS,
+            n => n,
+        };
+        let mut rest = digits;
+        let mut chunk_len = first_chunk;
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(chunk_len);
+            let chunk: u32 = chunk.parse().ok()?;
+            let factor = 10u32.pow(chunk_len as u32);
+            mul_small_add(&mut magnitude, factor, chunk);
+            rest = tail;
+            chunk_len = DECIMAL_DIGITS;
+        }
+        Some(Self::new(false, magnitude))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let abs = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &limb| (acc << BASE_BITS) | limb as u64);
+        if self.negative {
+            // `i64::MIN` has a magnitude one larger than `i64::MAX`.
+            (abs <= i64::MIN.unsigned_abs()).then(|| (abs as i64).wrapping_neg())
+        } else {
+            i64::try_from(abs).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let abs = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 4294967296.0 + limb as f64);
+        if self.negative {
+            -abs
+        } else {
+            abs
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.magnitude.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> Self {
+        if self.negative == other.negative {
+            return Self::new(
+                self.negative,
+                add_magnitudes(&self.magnitude, &other.magnitude),
+            );
+        }
+        match compare_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Self::new(
+                other.negative,
+                sub_magnitudes(&other.magnitude, &self.magnitude),
+            ),
+            _ => Self::new(
+                self.negative,
+                sub_magnitudes(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> Self {
+        Self::new(
+            self.negative != other.negative,
+            mul_magnitudes(&self.magnitude, &other.magnitude),
+        )
+    }
+
+    /// Truncating division, matching Rust's `/` and `%` on primitive
+    /// integers: the quotient rounds toward zero and the remainder takes the
+    /// sign of `self`. Returns `None` when dividing by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitudes(&self.magnitude, &other.magnitude);
+        Some((
+            Self::new(self.negative != other.negative, quotient),
+            Self::new(self.negative, remainder),
+        ))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, DECIMAL_BASE));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.pop() {
+            Some(first) => write!(f, "{first}")?,
+            None => write!(f, "0")?,
+        }
+        for chunk in chunks.iter().rev() {
+            write!(f, "{chunk:0width$}", width = DECIMAL_DIGITS)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let sum = limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> BASE_BITS;
+    }
+    result.push(carry as u32);
+    trim(&mut result);
+    result
+}
+
+/// Computes `a - b`, where `a` must not be smaller than `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut difference = limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << BASE_BITS;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let product = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> BASE_BITS;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+/// Computes `magnitude * factor + addend` in place.
+fn mul_small_add(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for limb in magnitude.iter_mut() {
+        let product = *limb as u64 * factor as u64 + carry;
+        *limb = product as u32;
+        carry = product >> BASE_BITS;
+    }
+    if carry != 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides `magnitude` in place by a single limb, returning the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let current = (remainder << BASE_BITS) | *limb as u64;
+        *limb = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    trim(magnitude);
+    remainder as u32
+}
+
+/// Long division of magnitudes (Knuth, TAOCP vol. 2, algorithm D).
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let mut quotient = a.to_vec();
+        let remainder = div_rem_small(&mut quotient, b[0]);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+
+    // Normalize so that the divisor's top limb has its high bit set, which
+    // keeps each estimated quotient digit at most two too large.
+    let shift = b[b.len() - 1].leading_zeros();
+    let v = shift_left(b, shift);
+    let mut u = shift_left(a, shift);
+    if u.len() == a.len() {
+        u.push(0);
+    }
+    let n = v.len();
+    let m = u.len() - n - 1;
+    let base = 1u64 << BASE_BITS;
+    let mut quotient = vec![0u32; m + 1];
+
+    for j in (0..=m).rev() {
+        let numerator = ((u[j + n] as u64) << BASE_BITS) | u[j + n - 1] as u64;
+        let mut q_hat = numerator / v[n - 1] as u64;
+        let mut r_hat = numerator % v[n - 1] as u64;
+        while q_hat >= base
+            || q_hat * v[n - 2] as u64 > ((r_hat << BASE_BITS) | u[j + n - 2] as u64)
+        {
+            q_hat -= 1;
+            r_hat += v[n - 1] as u64;
+            if r_hat >= base {
+                break;
+            }
+        }
+
+        // Subtract `q_hat * v` from the current window of `u`.
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let product = q_hat * v[i] as u64 + carry;
+            carry = product >> BASE_BITS;
+            let difference = u[i + j] as i64 - borrow - (product as u32) as i64;
+            u[i + j] = difference as u32;
+            borrow = (difference < 0) as i64;
+        }
+        let difference = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = difference as u32;
+
+        // The estimate was one too large: add the divisor back.
+        if difference < 0 {
+            q_hat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> BASE_BITS;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = q_hat as u32;
+    }
+
+    trim(&mut quotient);
+    let mut remainder = shift_right(&u[..n], shift);
+    trim(&mut remainder);
+    (quotient, remainder)
+}
+
+fn shift_left(magnitude: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return magnitude.to_vec();
+    }
+    let mut result = Vec::with_capacity(magnitude.len() + 1);
+    let mut carry = 0u32;
+    for &limb in magnitude {
+        result.push((limb << shift) | carry);
+        carry = limb >> (BASE_BITS - shift);
+    }
+    if carry != 0 {
+        result.push(carry);
+    }
+    result
+}
+
+fn shift_right(magnitude: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return magnitude.to_vec();
+    }
+    let mut result = vec![0u32; magnitude.len()];
+    for i in 0..magnitude.len() {
+        let high = magnitude
+            .get(i + 1)
+            .map_or(0, |&limb| limb << (BASE_BITS - shift));
+        result[i] = (magnitude[i] >> shift) | high;
+    }
+    result
+}
@@ -0,0 +1,104 @@
+use crate::error::SaltError;
+use crate::span::Span;
+use crate::value::{Key, Value};
+
+const NAMES: [&str; 5] = ["len", "push", "pop", "contains", "keys"];
+
+/// Looks up the builtin function called `name`.
+pub fn lookup(name: &str) -> Option<Value> {
+    NAMES
+        .iter()
+        .find(|&&builtin| builtin == name)
+        .map(|&builtin| Value::Builtin(builtin))
+}
+
+/// Calls the builtin function `name`. Returns `None` if there is no such
+/// builtin, so that the caller can report an unknown function.
+pub fn call(name: &str, arguments: Vec<Value>, span: Span) -> Option<Result<Value, SaltError>> {
+    let result = match name {
+        "len" => len(arguments, span),
+        "push" => push(arguments, span),
+        "pop" => pop(arguments, span),
+        "contains" => contains(arguments, span),
+        "keys" => keys(arguments, span),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn len(arguments: Vec<Value>, span: Span) -> Result<Value, SaltError> {
+    let [value] = expect_arguments("len", arguments, span)?;
+    let length = match &value {
+        Value::Array(elements) => elements.borrow().len(),
+        Value::String(string) => string.chars().count(),
+        Value::Map(map) => map.borrow().len(),
+        other => return Err(type_error("len", other, span)),
+    };
+    Ok(Value::Integer(length as i64))
+}
+
+fn push(arguments: Vec<Value>, span: Span) -> Result<Value, SaltError> {
+    let [array, value] = expect_arguments("push", arguments, span)?;
+    match array {
+        Value::Array(elements) => {
+            elements.borrow_mut().push(value);
+            Ok(Value::Unit)
+        }
+        other => Err(type_error("push", &other, span)),
+    }
+}
+
+fn pop(arguments: Vec<Value>, span: Span) -> Result<Value, SaltError> {
+    let [array] = expect_arguments("pop", arguments, span)?;
+    match array {
+        Value::Array(elements) => elements
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| SaltError::runtime("Cannot pop from an empty Array", span)),
+        other => Err(type_error("pop", &other, span)),
+    }
+}
+
+/// Whether a map has a key, or an array has an element equal to a value.
+fn contains(arguments: Vec<Value>, span: Span) -> Result<Value, SaltError> {
+    let [collection, value] = expect_arguments("contains", arguments, span)?;
+    let contained = match collection {
+        Value::Map(map) => map.borrow().contains_key(&Key::from_value(&value, span)?),
+        Value::Array(elements) => elements.borrow().contains(&value),
+        other => return Err(type_error("contains", &other, span)),
+    };
+    Ok(Value::Boolean(contained))
+}
+
+/// The keys of a map as a new array, in insertion order.
+fn keys(arguments: Vec<Value>, span: Span) -> Result<Value, SaltError> {
+    let [map] = expect_arguments("keys", arguments, span)?;
+    match map {
+        Value::Map(map) => Ok(Value::array(
+            map.borrow().keys().map(Key::to_value).collect(),
+        )),
+        other => Err(type_error("keys", &other, span)),
+    }
+}
+
+fn expect_arguments<const N: usize>(
+    name: &str,
+    arguments: Vec<Value>,
+    span: Span,
+) -> Result<[Value; N], SaltError> {
+    let given = arguments.len();
+    arguments.try_into().map_err(|_| {
+        let plural = if N == 1 { "" } else { "s" };
+        SaltError::runtime(
+            format!("Function `{name}` expects {N} argument{plural} but was given {given}"),
+            span,
+        )
+    })
+}
+
+fn type_error(name: &str, value: &Value, span: Span) -> SaltError {
+    SaltError::runtime(
+        format!("Type error: cannot apply `{name}` to {}", value.type_name()),
+        span,
+    )
+}
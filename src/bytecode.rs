@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{BinaryOpType, UnaryOpType};
+use crate::error::SaltError;
+use crate::span::Span;
+use crate::value::Value;
+
+/// A single VM instruction. Operands are indices into the pools of the
+/// `Code` the instruction belongs to, or into the tables of the `Program`
+/// for functions and globals; fields named `span` index `Code::spans` and
+/// say where a runtime error points.
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction {
+    /// Pushes the constant at the given index.
+    Constant(u32),
+    Unit,
+    Pop,
+    GetLocal(u32),
+    /// Pops a value into a local slot.
+    SetLocal(u32),
+    /// Pushes a global, raising `errors[error]` if its initializer has not
+    /// run yet.
+    GetGlobal {
+        global: u32,
+        error: u32,
+    },
+    /// Pops a value into a global.
+    SetGlobal(u32),
+    /// Pushes a named function of the program.
+    Function(u32),
+    /// Pushes a lambda, capturing the values of the local slots listed in
+    /// its `Code::captures`.
+    Closure(u32),
+    Raise(u32),
+    Unary {
+        op: UnaryOpType,
+        span: u32,
+    },
+    Binary {
+        op: BinaryOpType,
+        span: u32,
+    },
+    /// Pops the left operand of `&&` or `||`. If it decides the result, it
+    /// is pushed back and execution continues at `target`.
+    ShortCircuit {
+        op: BinaryOpType,
+        target: u32,
+        span: u32,
+    },
+    /// Checks that the right operand of `&&` or `||` is a Boolean.
+    LogicalOperand {
+        op: BinaryOpType,
+        span: u32,
+    },
+    Jump(u32),
+    /// Pops a condition and jumps to `target` if it is false.
+    JumpUnless {
+        target: u32,
+        span: u32,
+    },
+    /// Calls the value below the `arguments` on top of the stack. A `tail`
+    /// call to a function replaces the current frame; it is followed by a
+    /// `Return` for builtins, which push their result instead.
+    Call {
+        arguments: u32,
+        span: u32,
+        callee_span: u32,
+        tail: bool,
+    },
+    /// Calls a named function of the program with the `arguments` on top
+    /// of the stack, replacing the current frame if it is a `tail` call.
+    CallFunction {
+        function: u32,
+        arguments: u32,
+        span: u32,
+        tail: bool,
+    },
+    Return,
+    Print,
+    Time,
+    /// Pops the given number of elements into a new array.
+    Array(u32),
+    /// Pushes an empty map.
+    Map,
+    /// Checks that the value on top of the stack can be a map key.
+    CheckKey {
+        span: u32,
+    },
+    /// Pops a value and a key and inserts them into the map below.
+    InsertEntry,
+    /// Pops the fields of a struct literal, in source order, into a new
+    /// instance laid out by the given entry of `structs`.
+    Struct(u32),
+    /// Pops `fields` values into a new value of `variants[variant]`.
+    Enum {
+        variant: u32,
+        fields: u32,
+    },
+    /// Pops an index and a target and pushes the element.
+    Index {
+        span: u32,
+        target_span: u32,
+        index_span: u32,
+    },
+    /// Pops a value, an index and a target and sets the element.
+    SetIndex {
+        span: u32,
+        target_span: u32,
+        index_span: u32,
+    },
+    /// Pops a struct instance and pushes its field `names[name]`.
+    GetField {
+        name: u32,
+        span: u32,
+        target_span: u32,
+    },
+    /// Pops a value and a struct instance and sets its field `names[name]`.
+    SetField {
+        name: u32,
+        span: u32,
+        target_span: u32,
+    },
+    /// Checks that the value on top of the stack can bound a range.
+    RangeBound {
+        span: u32,
+    },
+    RangeStep {
+        span: u32,
+    },
+    /// Pops the step, end and start of a range and starts iterating over it.
+    IterRange {
+        inclusive: bool,
+    },
+    /// Pops an array or map and starts iterating over a snapshot of it.
+    IterItems {
+        span: u32,
+    },
+    /// Pushes the next item of the innermost iteration, or jumps to `exit`
+    /// once there are no more.
+    IterNext {
+        exit: u32,
+    },
+    /// Ends the innermost iteration.
+    IterEnd,
+    /// Pops a value and matches it against `patterns[pattern]`, storing
+    /// its bindings in local slots, or jumps to `otherwise`.
+    Match {
+        pattern: u32,
+        otherwise: u32,
+    },
+    /// Pops the scrutinee of a match that no arm matched.
+    NoMatch {
+        span: u32,
+    },
+}
+
+/// A compiled function, lambda or global initializer.
+#[derive(Debug, Default)]
+pub struct Code {
+    pub name: Rc<str>,
+    pub arity: usize,
+    /// The module the code was compiled from.
+    pub module: usize,
+    /// The signature, for errors about the number of arguments.
+    pub span: Span,
+    /// The number of local slots: the parameters first, then captured
+    /// variables and other locals.
+    pub slots: usize,
+    pub captures: Vec<Capture>,
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    pub spans: Vec<Span>,
+    /// Errors known at compile time, raised when they are reached.
+    pub errors: Vec<SaltError>,
+    pub structs: Vec<StructLayout>,
+    /// Enum variants as the module defining the enum, the enum name and
+    /// the variant name.
+    pub variants: Vec<(usize, String, String)>,
+    pub patterns: Vec<Pattern>,
+}
+
+/// A variable a lambda captures from the function creating it.
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    /// The slot of the variable in the creating function.
+    pub from: u32,
+    /// The slot it is copied to when the lambda is called.
+    pub to: u32,
+}
+
+/// How to build an instance from the fields of a struct literal.
+#[derive(Debug)]
+pub struct StructLayout {
+    pub name: String,
+    /// The module defining the struct.
+    pub module: usize,
+    /// The fields in definition order, each with the position of its value
+    /// among the fields of the literal.
+    pub fields: Vec<(String, usize)>,
+}
+
+/// A `match` pattern whose bindings are resolved to local slots.
+#[derive(Debug)]
+pub enum Pattern {
+    Wildcard,
+    Binding(u32),
+    Literal(Value),
+    Variant {
+        /// The module defining the enum.
+        module: usize,
+        enum_name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
+}
+
+/// A whole program compiled for the VM.
+#[derive(Debug)]
+pub struct Program {
+    /// Named functions first, then lambdas.
+    pub functions: Vec<Rc<Code>>,
+    pub globals: usize,
+    /// Code evaluating the globals of each module, in the order the
+    /// modules are loaded.
+    pub initializers: Vec<Rc<Code>>,
+    /// The `main` function of the entry module.
+    pub main: Option<u32>,
+    /// Struct definitions by module and name, for errors about fields.
+    pub structs: HashMap<(usize, String), Span>,
+}
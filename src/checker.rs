@@ -0,0 +1,497 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    Arm, Assignment, Block, Else, Enum, EnumLiteral, Expr, Global, IfStmt, Iterable, Match, Name,
+    Pattern, Program, Statement, Target,
+};
+use crate::error::{ErrorKind, SaltError};
+use crate::ops;
+use crate::span::Span;
+use crate::value::Value;
+
+/// Checks a parsed program before it runs: every enum literal and pattern
+/// must name an existing variant with the right number of fields, and every
+/// `match` must be exhaustive. Types qualified with a module must be those
+/// of one of the `imports`, given by the name they are imported as.
+pub fn check(program: &Program, imports: &HashMap<&str, &Program>) -> Vec<SaltError> {
+    let modules = [(None, program)].into_iter().chain(
+        imports
+            .iter()
+            .map(|(&name, &program)| (Some(name), program)),
+    );
+    let mut enums = HashMap::new();
+    for (module, program) in modules {
+        for global in &program.globals {
+            if let Global::Enum(definition) = global {
+                enums.insert(qualified(module, &definition.name), definition);
+            }
+        }
+    }
+    let mut checker = Checker {
+        enums,
+        modules: imports.keys().copied().collect(),
+        errors: Vec::new(),
+    };
+    for global in &program.globals {
+        match global {
+            Global::Function(function) => checker.check_block(&function.block),
+            Global::Variable(variable) => checker.check_expr(&variable.expr),
+            Global::Struct(_) | Global::Enum(_) | Global::Import(_) => (),
+        }
+    }
+    checker.errors
+}
+
+struct Checker<'a> {
+    /// The enums of the module and of its imports, by qualified name.
+    enums: HashMap<String, &'a Enum>,
+    /// The names of the imported modules.
+    modules: HashSet<&'a str>,
+    errors: Vec<SaltError>,
+}
+
+impl<'a> Checker<'a> {
+    fn check_block(&mut self, block: &Block) {
+        for statement in &block.statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(let_stmt) => self.check_expr(&let_stmt.expr),
+            Statement::If(if_stmt) => self.check_if(if_stmt),
+            Statement::While(while_loop) => {
+                self.check_expr(&while_loop.condition);
+                self.check_block(&while_loop.body);
+            }
+            Statement::For(for_loop) => {
+                match &for_loop.iterable {
+                    Iterable::Range(range) => {
+                        self.check_expr(&range.start);
+                        self.check_expr(&range.end);
+                        if let Some(step) = &range.step {
+                            self.check_expr(step);
+                        }
+                    }
+                    Iterable::Expr(expr) => self.check_expr(expr),
+                }
+                self.check_block(&for_loop.body);
+            }
+            Statement::Return(return_stmt) => self.check_expr(&return_stmt.expr),
+            Statement::Expr(expr) => self.check_expr(expr),
+            Statement::Assignment(assignment) => self.check_assignment(assignment),
+            Statement::Print(print) => self.check_expr(&print.expr),
+            Statement::Break(_) | Statement::Continue(_) => (),
+            Statement::Match(match_stmt) => self.check_match(match_stmt, Self::check_block),
+        }
+    }
+
+    fn check_if(&mut self, if_stmt: &IfStmt) {
+        self.check_expr(&if_stmt.condition);
+        self.check_block(&if_stmt.body);
+        match &if_stmt.else_branch {
+            Some(Else::Block(block)) => self.check_block(block),
+            Some(Else::If(else_if)) => self.check_if(else_if),
+            None => (),
+        }
+    }
+
+    fn check_assignment(&mut self, assignment: &Assignment) {
+        match &assignment.target {
+            Target::Name(_) => (),
+            Target::Index(index) => {
+                self.check_expr(&index.target);
+                self.check_expr(&index.index);
+            }
+            Target::Field(field) => self.check_expr(&field.target),
+        }
+        self.check_expr(&assignment.expr);
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) | Expr::Name(_) | Expr::Time(_) => (),
+            Expr::Call(call) => {
+                self.check_expr(&call.callee);
+                self.check_exprs(&call.arguments);
+            }
+            Expr::Lambda(lambda) => self.check_block(&lambda.function.block),
+            Expr::Array(array) => self.check_exprs(&array.elements),
+            Expr::Map(map) => {
+                for (key, value) in &map.entries {
+                    self.check_expr(key);
+                    self.check_expr(value);
+                }
+            }
+            Expr::StructLiteral(literal) => {
+                if let Err(error) = self.check_module(literal.module.as_ref()) {
+                    self.errors.push(error);
+                }
+                for (_, value) in &literal.fields {
+                    self.check_expr(value);
+                }
+            }
+            Expr::EnumLiteral(literal) => self.check_enum_literal(literal),
+            Expr::Index(index) => {
+                self.check_expr(&index.target);
+                self.check_expr(&index.index);
+            }
+            Expr::Field(field) => self.check_expr(&field.target),
+            Expr::UnaryOp(unary_op) => self.check_expr(&unary_op.expr),
+            Expr::BinaryOp(binary_op) => {
+                self.check_expr(&binary_op.left);
+                self.check_expr(&binary_op.right);
+            }
+            Expr::Match(match_expr) => self.check_match(match_expr, Self::check_expr),
+        }
+    }
+
+    fn check_exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.check_expr(expr);
+        }
+    }
+
+    fn check_enum_literal(&mut self, literal: &EnumLiteral) {
+        self.check_exprs(&literal.arguments);
+        if let Err(error) = self.check_variant(
+            literal.module.as_ref(),
+            &literal.enum_name,
+            &literal.variant,
+            literal.arguments.len(),
+            literal.span,
+        ) {
+            self.errors.push(error);
+        }
+    }
+
+    /// Checks that a type qualified with `module` names an imported module.
+    fn check_module(&self, module: Option<&Name>) -> Result<(), SaltError> {
+        match module {
+            Some(module) if !self.modules.contains(module.name.as_str()) => Err(check_error(
+                format!("No such module: {}", module.name),
+                module.span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `enum_name::variant` of `module` exists and has `arity`
+    /// fields.
+    fn check_variant(
+        &self,
+        module: Option<&Name>,
+        enum_name: &str,
+        variant: &Name,
+        arity: usize,
+        span: Span,
+    ) -> Result<(), SaltError> {
+        self.check_module(module)?;
+        let enum_name = qualified(module.map(|module| module.name.as_str()), enum_name);
+        let Some(definition) = self.enums.get(&enum_name) else {
+            return Err(check_error(format!("No such enum: {enum_name}"), span));
+        };
+        let Some(declared) = definition.variants.iter().find(|v| v.name == variant.name) else {
+            return Err(check_error(
+                format!("Enum `{enum_name}` has no variant `{}`", variant.name),
+                variant.span,
+            )
+            .with_note(
+                format!("enum `{enum_name}` defined here"),
+                Some(definition.span),
+            ));
+        };
+        let expected = declared.fields.len();
+        if expected != arity {
+            let plural = if expected == 1 { "" } else { "s" };
+            return Err(check_error(
+                format!(
+                    "Variant `{enum_name}::{}` has {expected} field{plural} but was given {arity}",
+                    variant.name
+                ),
+                span,
+            )
+            .with_note(
+                format!("variant `{}` declared here", variant.name),
+                Some(declared.span),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_match<T>(&mut self, match_: &Match<T>, check_body: fn(&mut Self, &T)) {
+        self.check_expr(&match_.scrutinee);
+        let mut valid = true;
+        for Arm { pattern, body, .. } in &match_.arms {
+            valid &= self.check_pattern(pattern);
+            check_body(self, body);
+        }
+        // Exhaustiveness is only meaningful once the patterns themselves are
+        // known to be well formed.
+        if !valid {
+            return;
+        }
+        let rows: Vec<Vec<Pat>> = match_
+            .arms
+            .iter()
+            .map(|arm| vec![Pat::from_pattern(&arm.pattern)])
+            .collect();
+        if let Some(witness) = self.find_uncovered(&rows, &[Pat::Wild]) {
+            self.errors.push(check_error(
+                format!("Non-exhaustive match: `{}` is not covered", witness[0]),
+                match_.span,
+            ));
+        }
+    }
+
+    /// Checks the variant patterns within `pattern`, reporting any errors.
+    /// Returns whether the pattern is well formed.
+    fn check_pattern(&mut self, pattern: &Pattern) -> bool {
+        let Pattern::Variant(variant) = pattern else {
+            return true;
+        };
+        let mut valid = match self.check_variant(
+            variant.module.as_ref(),
+            &variant.enum_name,
+            &variant.variant,
+            variant.fields.len(),
+            variant.span,
+        ) {
+            Ok(()) => true,
+            Err(error) => {
+                self.errors.push(error);
+                false
+            }
+        };
+        for field in &variant.fields {
+            valid &= self.check_pattern(field);
+        }
+        valid
+    }
+
+    /// Searches for values that match `vector` but none of `rows`, following
+    /// Maranget's usefulness algorithm ("Warnings for pattern matching",
+    /// 2007). Returns a witness: one pattern per column of `vector`
+    /// describing such values.
+    fn find_uncovered(&self, rows: &[Vec<Pat>], vector: &[Pat]) -> Option<Vec<Pat>> {
+        let Some((head, tail)) = vector.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        match head {
+            Pat::Ctor(ctor, fields) => {
+                let vector = [fields.as_slice(), tail].concat();
+                let witness = self.find_uncovered(&specialize(rows, ctor), &vector)?;
+                Some(rebuild(ctor, witness))
+            }
+            Pat::Wild => {
+                let used: Vec<&Ctor> = rows
+                    .iter()
+                    .filter_map(|row| match &row[0] {
+                        Pat::Ctor(ctor, _) => Some(ctor),
+                        Pat::Wild => None,
+                    })
+                    .collect();
+                match self.missing_ctors(&used) {
+                    // Every constructor of the type appears: the values are
+                    // uncovered only if they are for one of them.
+                    Some(missing) if missing.is_empty() => {
+                        self.complete_ctors(used[0]).into_iter().find_map(|ctor| {
+                            let wilds = vec![Pat::Wild; ctor.arity()];
+                            let vector = [wilds.as_slice(), tail].concat();
+                            let witness = self.find_uncovered(&specialize(rows, &ctor), &vector)?;
+                            Some(rebuild(&ctor, witness))
+                        })
+                    }
+                    missing => {
+                        let defaults: Vec<Vec<Pat>> = rows
+                            .iter()
+                            .filter(|row| matches!(row[0], Pat::Wild))
+                            .map(|row| row[1..].to_vec())
+                            .collect();
+                        let mut witness = self.find_uncovered(&defaults, tail)?;
+                        let head = match missing.and_then(|missing| missing.into_iter().next()) {
+                            Some(ctor) => {
+                                let wilds = vec![Pat::Wild; ctor.arity()];
+                                Pat::Ctor(ctor, wilds)
+                            }
+                            None => Pat::Wild,
+                        };
+                        witness.insert(0, head);
+                        Some(witness)
+                    }
+                }
+            }
+        }
+    }
+
+    /// The constructors of the type of `used` that do not appear in it, or
+    /// `None` when that type has too many values to list.
+    fn missing_ctors(&self, used: &[&Ctor]) -> Option<Vec<Ctor>> {
+        let first = used.first()?;
+        let all = self.complete_ctors(first);
+        if all.is_empty() {
+            return None;
+        }
+        Some(
+            all.into_iter()
+                .filter(|ctor| !used.contains(&ctor))
+                .collect(),
+        )
+    }
+
+    /// Every constructor of the type `ctor` belongs to, or none for types
+    /// such as Integer whose values cannot be listed.
+    fn complete_ctors(&self, ctor: &Ctor) -> Vec<Ctor> {
+        match ctor {
+            Ctor::Boolean(_) => vec![Ctor::Boolean(false), Ctor::Boolean(true)],
+            Ctor::Variant { enum_name, .. } => self.enums[enum_name]
+                .variants
+                .iter()
+                .map(|variant| Ctor::Variant {
+                    enum_name: enum_name.clone(),
+                    variant: variant.name.clone(),
+                    arity: variant.fields.len(),
+                })
+                .collect(),
+            Ctor::Literal(_) => Vec::new(),
+        }
+    }
+}
+
+fn check_error(message: impl Into<String>, span: Span) -> SaltError {
+    SaltError::new(ErrorKind::Check, message, Some(span))
+}
+
+/// The name of the type `name` of the imported `module`, or of the current
+/// module, as it is written in the source.
+fn qualified(module: Option<&str>, name: &str) -> String {
+    match module {
+        Some(module) => format!("{module}.{name}"),
+        None => name.to_string(),
+    }
+}
+
+/// A simplified pattern for exhaustiveness checking, where bindings are
+/// wildcards.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+#[derive(Debug, Clone)]
+enum Ctor {
+    Boolean(bool),
+    Variant {
+        /// The qualified name of the enum.
+        enum_name: String,
+        variant: String,
+        arity: usize,
+    },
+    /// Any other literal, from a type with too many values to list.
+    Literal(Value),
+}
+
+/// Literals are the same constructor when they match the same values, so
+/// `1` and `1.0` are.
+impl PartialEq for Ctor {
+    fn eq(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (Ctor::Boolean(l), Ctor::Boolean(r)) => l == r,
+            (
+                Ctor::Variant {
+                    enum_name,
+                    variant,
+                    arity,
+                },
+                Ctor::Variant {
+                    enum_name: other_enum,
+                    variant: other_variant,
+                    arity: other_arity,
+                },
+            ) => enum_name == other_enum && variant == other_variant && arity == other_arity,
+            (Ctor::Literal(l), Ctor::Literal(r)) => ops::equal(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl Ctor {
+    fn arity(&self) -> usize {
+        match self {
+            Ctor::Variant { arity, .. } => *arity,
+            Ctor::Boolean(_) | Ctor::Literal(_) => 0,
+        }
+    }
+}
+
+impl Pat {
+    fn from_pattern(pattern: &Pattern) -> Pat {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(_) => Pat::Wild,
+            Pattern::Literal(literal) => match literal.value {
+                Value::Boolean(b) => Pat::Ctor(Ctor::Boolean(b), Vec::new()),
+                ref value => Pat::Ctor(Ctor::Literal(value.clone()), Vec::new()),
+            },
+            Pattern::Variant(variant) => Pat::Ctor(
+                Ctor::Variant {
+                    enum_name: qualified(
+                        variant.module.as_ref().map(|module| module.name.as_str()),
+                        &variant.enum_name,
+                    ),
+                    variant: variant.variant.name.clone(),
+                    arity: variant.fields.len(),
+                },
+                variant.fields.iter().map(Pat::from_pattern).collect(),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Pat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Ctor(Ctor::Boolean(b), _) => write!(f, "{b}"),
+            Pat::Ctor(Ctor::Literal(value), _) => write!(f, "{value}"),
+            Pat::Ctor(
+                Ctor::Variant {
+                    enum_name, variant, ..
+                },
+                fields,
+            ) => {
+                write!(f, "{enum_name}::{variant}")?;
+                if !fields.is_empty() {
+                    let fields: Vec<String> = fields.iter().map(Pat::to_string).collect();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The rows that can match values built with `ctor`, with the head pattern
+/// replaced by its fields.
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let fields = match &row[0] {
+                Pat::Ctor(head, fields) if head == ctor => fields.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Wild => vec![Pat::Wild; ctor.arity()],
+            };
+            Some([fields.as_slice(), &row[1..]].concat())
+        })
+        .collect()
+}
+
+/// Undoes `specialize` on a witness, folding the leading fields back into a
+/// pattern for `ctor`.
+fn rebuild(ctor: &Ctor, mut witness: Vec<Pat>) -> Vec<Pat> {
+    let rest = witness.split_off(ctor.arity());
+    let mut rebuilt = vec![Pat::Ctor(ctor.clone(), witness)];
+    rebuilt.extend(rest);
+    rebuilt
+}
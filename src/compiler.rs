@@ -0,0 +1,823 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{
+    Assignment, BinaryOp, BinaryOpType, Block, Call, Else, EnumLiteral, Expr, Field, ForLoop,
+    Function, Global, IfStmt, Iterable, Match, Name, Statement, Struct, StructLiteral, Target,
+    WhileLoop,
+};
+use crate::builtins;
+use crate::bytecode::{Capture, Code, Instruction, Pattern, Program, StructLayout};
+use crate::error::SaltError;
+use crate::module::Module;
+use crate::ops;
+use crate::span::Span;
+use crate::value::Value;
+
+/// Compiles loaded modules, each after the modules it imports, for the VM.
+/// Local variables use the slots the resolver assigned. Errors the
+/// tree-walking interpreter only finds when it reaches them, such as a
+/// missing member of an imported module, are compiled into instructions
+/// raising the same error.
+pub fn compile(modules: &[Module]) -> Program {
+    let mut compiler = Compiler::new(modules);
+    let initializers = modules
+        .iter()
+        .map(|module| compiler.compile_module(module))
+        .collect();
+    let main = modules.last().and_then(|entry| {
+        let scope = &compiler.scopes[&entry.file];
+        scope.functions.get("main").copied()
+    });
+    let structs = compiler
+        .scopes
+        .iter()
+        .flat_map(|(&module, scope)| {
+            scope
+                .structs
+                .iter()
+                .map(move |(&name, definition)| ((module, name.to_string()), definition.span))
+        })
+        .collect();
+    Program {
+        functions: compiler
+            .functions
+            .into_iter()
+            .map(|code| code.expect("every named function is compiled"))
+            .collect(),
+        globals: compiler.globals,
+        initializers,
+        main,
+        structs,
+    }
+}
+
+/// The names a module defines, resolved to indices into the program.
+struct Scope<'a> {
+    functions: HashMap<&'a str, u32>,
+    globals: HashMap<&'a str, u32>,
+    structs: HashMap<&'a str, &'a Struct>,
+    imports: &'a HashMap<String, usize>,
+}
+
+/// What a name refers to.
+enum Resolved {
+    Local(u32),
+    Global(u32),
+    Function(u32),
+    Builtin(Value),
+}
+
+struct Loop {
+    /// Where `continue` jumps to.
+    start: usize,
+    /// The jumps of `break`s, patched once the end of the loop is known.
+    breaks: Vec<usize>,
+}
+
+/// A function being compiled.
+struct FunctionState {
+    code: Code,
+    loops: Vec<Loop>,
+}
+
+struct Compiler<'a> {
+    scopes: HashMap<usize, Scope<'a>>,
+    /// Named functions, indexed before any is compiled so that they can
+    /// call each other, followed by lambdas.
+    functions: Vec<Option<Rc<Code>>>,
+    globals: usize,
+    /// The functions being compiled, with lambdas inside the function
+    /// creating them.
+    states: Vec<FunctionState>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(modules: &'a [Module]) -> Self {
+        let mut compiler = Self {
+            scopes: HashMap::new(),
+            functions: Vec::new(),
+            globals: 0,
+            states: Vec::new(),
+        };
+        for module in modules {
+            let mut scope = Scope {
+                functions: HashMap::new(),
+                globals: HashMap::new(),
+                structs: HashMap::new(),
+                imports: &module.imports,
+            };
+            for global in &module.program.globals {
+                match global {
+                    Global::Function(function) => {
+                        scope
+                            .functions
+                            .insert(&function.name, compiler.functions.len() as u32);
+                        compiler.functions.push(None);
+                    }
+                    Global::Variable(variable) => {
+                        scope
+                            .globals
+                            .insert(&variable.name, compiler.globals as u32);
+                        compiler.globals += 1;
+                    }
+                    Global::Struct(definition) => {
+                        scope.structs.insert(&definition.name, definition);
+                    }
+                    Global::Enum(_) | Global::Import(_) => (),
+                }
+            }
+            compiler.scopes.insert(module.file, scope);
+        }
+        compiler
+    }
+
+    /// Compiles the functions of `module` and returns the code evaluating
+    /// its globals.
+    fn compile_module(&mut self, module: &Module) -> Rc<Code> {
+        for global in &module.program.globals {
+            if let Global::Function(function) = global {
+                let index = self.scopes[&module.file].functions[function.name.as_str()];
+                let code = self.compile_function(function);
+                self.functions[index as usize] = Some(code);
+            }
+        }
+        // The initializers run one after the other, so they can share slots.
+        let slots = module
+            .program
+            .globals
+            .iter()
+            .map(|global| match global {
+                Global::Variable(variable) => variable.slots,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        self.begin_function(Code {
+            name: "<globals>".into(),
+            module: module.file,
+            slots,
+            ..Code::default()
+        });
+        for global in &module.program.globals {
+            if let Global::Variable(variable) = global {
+                self.compile_expression(&variable.expr);
+                let id = self.scopes[&module.file].globals[variable.name.as_str()];
+                self.emit(Instruction::SetGlobal(id));
+            }
+        }
+        self.end_function()
+    }
+
+    fn compile_function(&mut self, function: &Function) -> Rc<Code> {
+        let captures = function
+            .captures
+            .iter()
+            .map(|capture| Capture {
+                from: capture.from as u32,
+                to: capture.to as u32,
+            })
+            .collect();
+        self.begin_function(Code {
+            name: function.name.as_str().into(),
+            arity: function.parameters.len(),
+            module: function.span.file,
+            span: function.span,
+            slots: function.slots,
+            captures,
+            ..Code::default()
+        });
+        self.compile_block(&function.block);
+        self.end_function()
+    }
+
+    fn begin_function(&mut self, code: Code) {
+        self.states.push(FunctionState {
+            code,
+            loops: Vec::new(),
+        });
+    }
+
+    /// Returns `()` if the end of the function is reached without a
+    /// `return`.
+    fn end_function(&mut self) -> Rc<Code> {
+        self.emit(Instruction::Unit);
+        self.emit(Instruction::Return);
+        let state = self.states.pop().expect("a function is being compiled");
+        Rc::new(state.code)
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .expect("a function is being compiled")
+    }
+
+    fn code(&mut self) -> &mut Code {
+        &mut self.state().code
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let instructions = &mut self.code().instructions;
+        instructions.push(instruction);
+        instructions.len() - 1
+    }
+
+    /// The index of the next instruction.
+    fn here(&mut self) -> usize {
+        self.code().instructions.len()
+    }
+
+    /// Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let here = self.here() as u32;
+        match &mut self.code().instructions[at] {
+            Instruction::Jump(target)
+            | Instruction::JumpUnless { target, .. }
+            | Instruction::ShortCircuit { target, .. }
+            | Instruction::IterNext { exit: target }
+            | Instruction::Match {
+                otherwise: target, ..
+            } => *target = here,
+            other => unreachable!("{other:?} is not a jump"),
+        }
+    }
+
+    fn span(&mut self, span: Span) -> u32 {
+        let spans = &mut self.code().spans;
+        spans.push(span);
+        (spans.len() - 1) as u32
+    }
+
+    fn constant(&mut self, value: Value) -> u32 {
+        let constants = &mut self.code().constants;
+        constants.push(value);
+        (constants.len() - 1) as u32
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        let names = &mut self.code().names;
+        match names.iter().position(|existing| existing == name) {
+            Some(i) => i as u32,
+            None => {
+                names.push(name.to_string());
+                (names.len() - 1) as u32
+            }
+        }
+    }
+
+    fn raise(&mut self, error: SaltError) {
+        let errors = &mut self.code().errors;
+        errors.push(error);
+        let index = (errors.len() - 1) as u32;
+        self.emit(Instruction::Raise(index));
+    }
+
+    fn module(&mut self) -> usize {
+        self.code().module
+    }
+
+    /// The module defining a struct or enum qualified with `module` in the
+    /// code being compiled. The checker made sure that a qualified type
+    /// names an import.
+    fn type_module(&mut self, module: Option<&Name>) -> usize {
+        let current = self.module();
+        match module {
+            Some(module) => self.scopes[&current].imports[&module.name],
+            None => current,
+        }
+    }
+
+    /// A slot, after those of the resolved locals, for a value the compiled
+    /// code keeps without naming it.
+    fn hidden_slot(&mut self) -> u32 {
+        let code = self.code();
+        code.slots += 1;
+        (code.slots - 1) as u32
+    }
+
+    /// Finds what `name` refers to: the local slot the resolver assigned it,
+    /// or else a definition of the current module or a builtin.
+    fn resolve(&mut self, name: &Name) -> Option<Resolved> {
+        if let Some(slot) = name.slot() {
+            return Some(Resolved::Local(slot as u32));
+        }
+        let module = self.module();
+        let scope = &self.scopes[&module];
+        if let Some(&global) = scope.globals.get(name.name.as_str()) {
+            return Some(Resolved::Global(global));
+        }
+        if let Some(&function) = scope.functions.get(name.name.as_str()) {
+            return Some(Resolved::Function(function));
+        }
+        builtins::lookup(&name.name).map(Resolved::Builtin)
+    }
+
+    /// The module that `target` refers to, if it is the name of an import
+    /// that no variable or function shadows.
+    fn imported_module<'e>(&mut self, target: &'e Expr) -> Option<(&'e str, usize)> {
+        let module = self.module();
+        ops::imported_module(target, self.scopes[&module].imports)
+    }
+
+    fn compile_block(&mut self, block: &Block) {
+        for statement in &block.statements {
+            self.compile_statement(statement);
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let(let_stmt) => {
+                self.compile_expression(&let_stmt.expr);
+                self.emit(Instruction::SetLocal(let_stmt.slot as u32));
+            }
+            Statement::If(if_stmt) => self.compile_if(if_stmt),
+            Statement::While(while_loop) => self.compile_while(while_loop),
+            Statement::For(for_loop) => self.compile_for(for_loop),
+            Statement::Return(return_stmt) => {
+                self.compile_tail(&return_stmt.expr);
+                self.emit(Instruction::Return);
+            }
+            Statement::Expr(expr) => {
+                self.compile_expression(expr);
+                self.emit(Instruction::Pop);
+            }
+            Statement::Assignment(assignment) => self.compile_assignment(assignment),
+            Statement::Print(print) => {
+                self.compile_expression(&print.expr);
+                self.emit(Instruction::Print);
+            }
+            Statement::Break(_) => {
+                let jump = self.emit(Instruction::Jump(0));
+                let innermost = self.state().loops.last_mut();
+                innermost
+                    .expect("the parser rejects `break` outside loops")
+                    .breaks
+                    .push(jump);
+            }
+            Statement::Continue(_) => {
+                let innermost = self.state().loops.last();
+                let start = innermost
+                    .expect("the parser rejects `continue` outside loops")
+                    .start;
+                self.emit(Instruction::Jump(start as u32));
+            }
+            Statement::Match(match_stmt) => self.compile_match(match_stmt, Self::compile_block),
+        }
+    }
+
+    fn compile_if(&mut self, if_stmt: &IfStmt) {
+        let skip_body = self.compile_condition(&if_stmt.condition);
+        self.compile_block(&if_stmt.body);
+        match &if_stmt.else_branch {
+            Some(else_branch) => {
+                let skip_else = self.emit(Instruction::Jump(0));
+                self.patch(skip_body);
+                match else_branch {
+                    Else::Block(block) => self.compile_block(block),
+                    Else::If(else_if) => self.compile_if(else_if),
+                }
+                self.patch(skip_else);
+            }
+            None => self.patch(skip_body),
+        }
+    }
+
+    /// Compiles a condition and a jump, to be patched, taken if it is false.
+    fn compile_condition(&mut self, condition: &Expr) -> usize {
+        self.compile_expression(condition);
+        let span = self.span(condition.span());
+        self.emit(Instruction::JumpUnless { target: 0, span })
+    }
+
+    fn compile_while(&mut self, while_loop: &WhileLoop) {
+        let start = self.here();
+        let exit = self.compile_condition(&while_loop.condition);
+        self.compile_loop_body(start, |compiler| compiler.compile_block(&while_loop.body));
+        self.emit(Instruction::Jump(start as u32));
+        self.patch(exit);
+        self.patch_breaks();
+    }
+
+    fn compile_for(&mut self, for_loop: &ForLoop) {
+        match &for_loop.iterable {
+            Iterable::Range(range) => {
+                self.compile_expression(&range.start);
+                let span = self.span(range.start.span());
+                self.emit(Instruction::RangeBound { span });
+                self.compile_expression(&range.end);
+                let span = self.span(range.end.span());
+                self.emit(Instruction::RangeBound { span });
+                match &range.step {
+                    Some(step) => {
+                        self.compile_expression(step);
+                        let span = self.span(step.span());
+                        self.emit(Instruction::RangeStep { span });
+                    }
+                    None => {
+                        let one = self.constant(Value::Integer(1));
+                        self.emit(Instruction::Constant(one));
+                    }
+                }
+                self.emit(Instruction::IterRange {
+                    inclusive: range.inclusive,
+                });
+            }
+            Iterable::Expr(expr) => {
+                self.compile_expression(expr);
+                let span = self.span(expr.span());
+                self.emit(Instruction::IterItems { span });
+            }
+        }
+        let start = self.here();
+        let exit = self.emit(Instruction::IterNext { exit: 0 });
+        self.compile_loop_body(start, |compiler| {
+            compiler.emit(Instruction::SetLocal(for_loop.slot as u32));
+            compiler.compile_block(&for_loop.body);
+        });
+        self.emit(Instruction::Jump(start as u32));
+        self.patch(exit);
+        self.patch_breaks();
+        self.emit(Instruction::IterEnd);
+    }
+
+    fn compile_loop_body(&mut self, start: usize, compile: impl FnOnce(&mut Self)) {
+        self.state().loops.push(Loop {
+            start,
+            breaks: Vec::new(),
+        });
+        compile(self);
+    }
+
+    /// Points the `break`s of the innermost loop to the next instruction.
+    fn patch_breaks(&mut self) {
+        let innermost = self.state().loops.pop().expect("a loop is being compiled");
+        for jump in innermost.breaks {
+            self.patch(jump);
+        }
+    }
+
+    fn compile_assignment(&mut self, assignment: &Assignment) {
+        match &assignment.target {
+            Target::Name(name) => {
+                self.compile_expression(&assignment.expr);
+                match self.resolve(name) {
+                    Some(Resolved::Local(slot)) => {
+                        self.emit(Instruction::SetLocal(slot));
+                    }
+                    Some(Resolved::Global(id)) => {
+                        self.emit(Instruction::SetGlobal(id));
+                    }
+                    _ => self.raise(
+                        SaltError::runtime(
+                            format!("Cannot assign to undeclared variable `{}`", name.name),
+                            assignment.span,
+                        )
+                        .with_note(
+                            format!("declare it first with `let {} = ...;`", name.name),
+                            None,
+                        ),
+                    ),
+                }
+            }
+            Target::Index(index) => {
+                self.compile_expression(&index.target);
+                self.compile_expression(&index.index);
+                self.compile_expression(&assignment.expr);
+                let spans = ops::IndexSpans::new(index);
+                let instruction = Instruction::SetIndex {
+                    span: self.span(spans.whole),
+                    target_span: self.span(spans.target),
+                    index_span: self.span(spans.index),
+                };
+                self.emit(instruction);
+            }
+            Target::Field(field) => {
+                if let Some((module_name, module)) = self.imported_module(&field.target) {
+                    self.compile_expression(&assignment.expr);
+                    let member = &field.field.name;
+                    match self.scopes[&module].globals.get(member.as_str()) {
+                        Some(&global) => {
+                            self.emit(Instruction::SetGlobal(global));
+                        }
+                        None => self.raise(ops::unknown_member(module_name, &field.field)),
+                    }
+                    return;
+                }
+                self.compile_expression(&field.target);
+                self.compile_expression(&assignment.expr);
+                let instruction = Instruction::SetField {
+                    name: self.name(&field.field.name),
+                    span: self.span(field.field.span),
+                    target_span: self.span(field.target.span()),
+                };
+                self.emit(instruction);
+            }
+        }
+    }
+
+    /// Runs the body of the first arm whose pattern matches the scrutinee,
+    /// with the pattern's bindings in their local slots.
+    fn compile_match<T>(&mut self, match_: &Match<T>, compile_body: fn(&mut Self, &T)) {
+        self.compile_expression(&match_.scrutinee);
+        let scrutinee = self.hidden_slot();
+        self.emit(Instruction::SetLocal(scrutinee));
+        let mut ends = Vec::new();
+        for arm in &match_.arms {
+            self.emit(Instruction::GetLocal(scrutinee));
+            let pattern = self.compile_pattern(&arm.pattern);
+            let patterns = &mut self.code().patterns;
+            patterns.push(pattern);
+            let pattern = (patterns.len() - 1) as u32;
+            let next_arm = self.emit(Instruction::Match {
+                pattern,
+                otherwise: 0,
+            });
+            compile_body(self, &arm.body);
+            ends.push(self.emit(Instruction::Jump(0)));
+            self.patch(next_arm);
+        }
+        self.emit(Instruction::GetLocal(scrutinee));
+        let span = self.span(match_.span);
+        self.emit(Instruction::NoMatch { span });
+        for end in ends {
+            self.patch(end);
+        }
+    }
+
+    fn compile_pattern(&mut self, pattern: &crate::ast::Pattern) -> Pattern {
+        match pattern {
+            crate::ast::Pattern::Wildcard(_) => Pattern::Wildcard,
+            crate::ast::Pattern::Binding(name) => {
+                let slot = name.slot().expect("bindings are resolved to slots");
+                Pattern::Binding(slot as u32)
+            }
+            crate::ast::Pattern::Literal(literal) => Pattern::Literal(literal.value.clone()),
+            crate::ast::Pattern::Variant(variant) => Pattern::Variant {
+                module: self.type_module(variant.module.as_ref()),
+                enum_name: variant.enum_name.clone(),
+                variant: variant.variant.name.clone(),
+                fields: variant
+                    .fields
+                    .iter()
+                    .map(|field| self.compile_pattern(field))
+                    .collect(),
+            },
+        }
+    }
+
+    fn compile_expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Literal(literal) => {
+                let constant = self.constant(literal.value.clone());
+                self.emit(Instruction::Constant(constant));
+            }
+            Expr::Name(name) => self.compile_name(name),
+            Expr::Call(call) => self.compile_call(call, false),
+            Expr::Lambda(lambda) => {
+                let code = self.compile_function(&lambda.function);
+                self.functions.push(Some(code));
+                let index = (self.functions.len() - 1) as u32;
+                self.emit(Instruction::Closure(index));
+            }
+            Expr::Array(array) => {
+                for element in &array.elements {
+                    self.compile_expression(element);
+                }
+                self.emit(Instruction::Array(array.elements.len() as u32));
+            }
+            Expr::Map(map) => {
+                self.emit(Instruction::Map);
+                for (key, value) in &map.entries {
+                    self.compile_expression(key);
+                    let span = self.span(key.span());
+                    self.emit(Instruction::CheckKey { span });
+                    self.compile_expression(value);
+                    self.emit(Instruction::InsertEntry);
+                }
+            }
+            Expr::StructLiteral(literal) => self.compile_struct_literal(literal),
+            Expr::EnumLiteral(literal) => self.compile_enum_literal(literal),
+            Expr::Index(index) => {
+                self.compile_expression(&index.target);
+                self.compile_expression(&index.index);
+                let spans = ops::IndexSpans::new(index);
+                let instruction = Instruction::Index {
+                    span: self.span(spans.whole),
+                    target_span: self.span(spans.target),
+                    index_span: self.span(spans.index),
+                };
+                self.emit(instruction);
+            }
+            Expr::Field(field) => self.compile_field(field),
+            Expr::UnaryOp(unary_op) => {
+                self.compile_expression(&unary_op.expr);
+                let span = self.span(unary_op.span);
+                self.emit(Instruction::Unary {
+                    op: unary_op.op_type,
+                    span,
+                });
+            }
+            Expr::BinaryOp(binary_op) => self.compile_binary_op(binary_op),
+            Expr::Time(_) => {
+                self.emit(Instruction::Time);
+            }
+            Expr::Match(match_expr) => self.compile_match(match_expr, Self::compile_expression),
+        }
+    }
+
+    fn compile_name(&mut self, name: &Name) {
+        match self.resolve(name) {
+            Some(Resolved::Local(slot)) => {
+                self.emit(Instruction::GetLocal(slot));
+            }
+            Some(Resolved::Global(global)) => self.get_global(global, ops::no_such_variable(name)),
+            Some(Resolved::Function(function)) => {
+                self.emit(Instruction::Function(function));
+            }
+            Some(Resolved::Builtin(builtin)) => {
+                let constant = self.constant(builtin);
+                self.emit(Instruction::Constant(constant));
+            }
+            None => self.raise(ops::no_such_variable(name)),
+        }
+    }
+
+    /// Pushes global `global`, raising `error` if it has not been
+    /// initialized yet, where the tree-walking interpreter does not see it.
+    fn get_global(&mut self, global: u32, error: SaltError) {
+        let errors = &mut self.code().errors;
+        errors.push(error);
+        let error = (errors.len() - 1) as u32;
+        self.emit(Instruction::GetGlobal { global, error });
+    }
+
+    /// Compiles a returned expression, making the calls in tail position,
+    /// including those in the arms of a match, tail calls.
+    fn compile_tail(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Call(call) => self.compile_call(call, true),
+            Expr::Match(match_expr) => self.compile_match(match_expr, Self::compile_tail),
+            expr => self.compile_expression(expr),
+        }
+    }
+
+    /// Compiles a call, as a tail call if it is returned right away.
+    fn compile_call(&mut self, call: &Call, tail: bool) {
+        let arguments = call.arguments.len() as u32;
+        if let Expr::Name(name) = call.callee.as_ref() {
+            match self.resolve(name) {
+                Some(Resolved::Function(function)) => {
+                    for argument in &call.arguments {
+                        self.compile_expression(argument);
+                    }
+                    let span = self.span(call.span);
+                    self.emit(Instruction::CallFunction {
+                        function,
+                        arguments,
+                        span,
+                        tail,
+                    });
+                    return;
+                }
+                Some(Resolved::Global(global)) => {
+                    self.get_global(global, ops::no_such_function(name, call.span));
+                }
+                None => {
+                    self.raise(ops::no_such_function(name, call.span));
+                    return;
+                }
+                _ => self.compile_name(name),
+            }
+        } else {
+            self.compile_expression(&call.callee);
+        }
+        for argument in &call.arguments {
+            self.compile_expression(argument);
+        }
+        let instruction = Instruction::Call {
+            arguments,
+            span: self.span(call.span),
+            callee_span: self.span(call.callee.span()),
+            tail,
+        };
+        self.emit(instruction);
+    }
+
+    fn compile_field(&mut self, field: &Field) {
+        if let Some((module_name, module)) = self.imported_module(&field.target) {
+            let scope = &self.scopes[&module];
+            let member = field.field.name.as_str();
+            if let Some(&global) = scope.globals.get(member) {
+                self.get_global(global, ops::unknown_member(module_name, &field.field));
+            } else if let Some(&function) = scope.functions.get(member) {
+                self.emit(Instruction::Function(function));
+            } else {
+                self.raise(ops::unknown_member(module_name, &field.field));
+            }
+            return;
+        }
+        self.compile_expression(&field.target);
+        let instruction = Instruction::GetField {
+            name: self.name(&field.field.name),
+            span: self.span(field.field.span),
+            target_span: self.span(field.target.span()),
+        };
+        self.emit(instruction);
+    }
+
+    fn compile_binary_op(&mut self, op: &BinaryOp) {
+        self.compile_expression(&op.left);
+        if let BinaryOpType::And | BinaryOpType::Or = op.op_type {
+            let span = self.span(op.left.span());
+            let short_circuit = self.emit(Instruction::ShortCircuit {
+                op: op.op_type,
+                target: 0,
+                span,
+            });
+            self.compile_expression(&op.right);
+            let span = self.span(op.right.span());
+            self.emit(Instruction::LogicalOperand {
+                op: op.op_type,
+                span,
+            });
+            self.patch(short_circuit);
+            return;
+        }
+        self.compile_expression(&op.right);
+        let span = self.span(op.span);
+        self.emit(Instruction::Binary {
+            op: op.op_type,
+            span,
+        });
+    }
+
+    /// Evaluates the fields in source order, as the tree-walking interpreter
+    /// does, raising its errors for unknown, repeated and missing fields.
+    fn compile_struct_literal(&mut self, literal: &StructLiteral) {
+        let module = self.type_module(literal.module.as_ref());
+        let Some(&definition) = self.scopes[&module].structs.get(literal.name.as_str()) else {
+            self.raise(ops::no_such_struct(literal));
+            return;
+        };
+        let mut positions: Vec<Option<usize>> = vec![None; definition.fields.len()];
+        for (position, (field, expr)) in literal.fields.iter().enumerate() {
+            let Some(i) = definition.fields.iter().position(|f| *f == field.name) else {
+                self.raise(ops::unknown_field(
+                    &definition.name,
+                    &field.name,
+                    field.span,
+                    Some(definition.span),
+                ));
+                return;
+            };
+            if positions[i].is_some() {
+                self.raise(SaltError::runtime(
+                    format!("Field `{}` is specified more than once", field.name),
+                    field.span,
+                ));
+                return;
+            }
+            positions[i] = Some(position);
+            self.compile_expression(expr);
+        }
+        let mut fields = Vec::new();
+        for (name, position) in definition.fields.iter().zip(positions) {
+            let Some(position) = position else {
+                self.raise(SaltError::runtime(
+                    format!("Missing field `{name}` in `{}` literal", definition.name),
+                    literal.span,
+                ));
+                return;
+            };
+            fields.push((name.clone(), position));
+        }
+        let structs = &mut self.code().structs;
+        structs.push(StructLayout {
+            name: definition.name.clone(),
+            module,
+            fields,
+        });
+        let layout = (structs.len() - 1) as u32;
+        self.emit(Instruction::Struct(layout));
+    }
+
+    fn compile_enum_literal(&mut self, literal: &EnumLiteral) {
+        for argument in &literal.arguments {
+            self.compile_expression(argument);
+        }
+        let module = self.type_module(literal.module.as_ref());
+        let variants = &mut self.code().variants;
+        variants.push((
+            module,
+            literal.enum_name.clone(),
+            literal.variant.name.clone(),
+        ));
+        let variant = (variants.len() - 1) as u32;
+        self.emit(Instruction::Enum {
+            variant,
+            fields: literal.arguments.len() as u32,
+        });
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt::Write;
+
+use crate::error::SaltError;
+use crate::source::SourceFile;
+use crate::span::Span;
+
+const TAB_WIDTH: usize = 4;
+
+/// Renders an error in the style of rustc: a header line, the offending
+/// source line with the span underlined, followed by any notes. Spans are
+/// looked up in `files`.
+pub fn render(error: &SaltError, files: &[SourceFile]) -> String {
+    let mut output = String::new();
+    let gutter = gutter_width(error);
+    writeln!(output, "{}: {}", error.kind, error.message).unwrap();
+    if let Some(span) = error.span {
+        render_snippet(&mut output, span, files, gutter);
+    }
+    for note in &error.notes {
+        if error.span.is_some() {
+            writeln!(output, "{:gutter$} |", "").unwrap();
+        }
+        writeln!(output, "note: {}", note.message).unwrap();
+        if let Some(span) = note.span {
+            render_snippet(&mut output, span, files, gutter);
+        }
+    }
+    output
+}
+
+fn gutter_width(error: &SaltError) -> usize {
+    let lines = error
+        .notes
+        .iter()
+        .filter_map(|note| note.span)
+        .chain(error.span)
+        .map(|span| span.line);
+    lines.max().unwrap_or(0).to_string().len()
+}
+
+fn render_snippet(output: &mut String, span: Span, files: &[SourceFile], gutter: usize) {
+    let file = &files[span.file];
+    let source = file.text.as_str();
+    writeln!(output, "{:gutter$}--> {}:{span}", "", file.name).unwrap();
+    let line_start = source[..span.offset.min(source.len())]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let line_end = source[line_start..]
+        .find('\n')
+        .map_or(source.len(), |i| line_start + i);
+    let line = &source[line_start..line_end];
+    let before = &source[line_start..span.offset.clamp(line_start, line_end)];
+    let spanned = &source[span.offset.clamp(line_start, line_end)
+        ..(span.offset + span.length).clamp(line_start, line_end)];
+
+    let padding = " ".repeat(display_width(before));
+    let underline = "^".repeat(display_width(spanned).max(1));
+    writeln!(output, "{:gutter$} |", "").unwrap();
+    let line = format!("{:>gutter$} | {}", span.line, expand_tabs(line));
+    writeln!(output, "{}", line.trim_end()).unwrap();
+    writeln!(output, "{:gutter$} | {padding}{underline}", "").unwrap();
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|ch| if ch == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
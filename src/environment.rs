@@ -1,23 +1,26 @@
-use std::collections::HashMap;
-
 use crate::value::Value;
 
+/// The local variables of a function call, in the slots the resolver
+/// assigned them.
 pub struct Environment {
-    values: HashMap<String, Value>,
+    slots: Vec<Value>,
+    /// The module whose globals and functions are in scope.
+    pub module: usize,
 }
 
 impl Environment {
-    pub fn new() -> Self {
+    pub fn new(module: usize, slots: usize) -> Self {
         Self {
-            values: HashMap::new(),
+            slots: vec![Value::Unit; slots],
+            module,
         }
     }
 
-    pub fn set(&mut self, name: String, value: Value) {
-        self.values.insert(name, value);
+    pub fn get(&self, slot: usize) -> &Value {
+        &self.slots[slot]
     }
 
-    pub fn get(&self, name: &String) -> Option<&Value> {
-        self.values.get(name)
+    pub fn set(&mut self, slot: usize, value: Value) {
+        self.slots[slot] = value;
     }
 }
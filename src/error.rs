@@ -0,0 +1,86 @@
+use std::{error, fmt};
+
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Lex,
+    Parse,
+    /// Found while resolving the `import`s of a program.
+    Import,
+    /// Found by the checker or the resolver, after parsing but before
+    /// execution.
+    Check,
+    Runtime,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Lex => write!(f, "lex error"),
+            ErrorKind::Parse => write!(f, "parse error"),
+            ErrorKind::Import => write!(f, "import error"),
+            ErrorKind::Check => write!(f, "error"),
+            ErrorKind::Runtime => write!(f, "runtime error"),
+        }
+    }
+}
+
+/// Additional context attached to an error, optionally pointing at another
+/// location in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaltError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where the error occurred, if it can be tied to the source at all.
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+}
+
+impl SaltError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, span: Option<Span>) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            span,
+        });
+        self
+    }
+
+    pub fn lex(message: impl Into<String>, span: Span) -> Self {
+        Self::new(ErrorKind::Lex, message, Some(span))
+    }
+
+    pub fn parse(message: impl Into<String>, span: Span) -> Self {
+        Self::new(ErrorKind::Parse, message, Some(span))
+    }
+
+    pub fn runtime(message: impl Into<String>, span: Span) -> Self {
+        Self::new(ErrorKind::Runtime, message, Some(span))
+    }
+}
+
+impl fmt::Display for SaltError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {span}: {}", self.kind, self.message),
+            None => write!(f, "{}: {}", self.kind, self.message),
+        }
+    }
+}
+
+impl error::Error for SaltError {}
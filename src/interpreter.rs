@@ -1,218 +1,703 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::rc::Rc;
 
 use crate::ast::{
-    BinaryOp, BinaryOpType, Assignment, Block, Expr, Function, Global, IfStmt, Print, Program, Return,
-    Statement, Time, UnaryOp, UnaryOpType, WhileLoop,
+    Assignment, BinaryOp, BinaryOpType, Block, Call, Else, Expr, ForLoop, Function, Global, IfStmt,
+    Index, Iterable, Let, Match, Name, Pattern, Print, Return, Statement, Struct, StructLiteral,
+    Target, Time, UnaryOp, WhileLoop,
 };
+use crate::builtins;
 use crate::environment::Environment;
-use crate::value::Value;
+use crate::error::{ErrorKind, SaltError};
+use crate::module::Module;
+use crate::ops::{self, IndexSpans, RangeIter};
+use crate::span::Span;
+use crate::value::{Callable, Closure, EnumValue, Instance, Key, Map, Value};
+
+/// How control leaves a statement.
+enum ControlFlow {
+    Normal,
+    Return(Value),
+    /// `return` of a call, which is made by the caller once the current
+    /// function has returned, so tail calls do not grow the Rust stack.
+    TailCall(EvaluatedCall),
+    Break,
+    Continue,
+}
+
+/// A call whose callee and arguments have been evaluated.
+struct EvaluatedCall {
+    callee: Value,
+    arguments: Vec<Value>,
+    span: Span,
+    callee_span: Span,
+}
+
+/// The current value of a `const` or `global` declaration.
+struct GlobalSlot {
+    value: RefCell<Value>,
+}
+
+/// The definitions of a module. Modules are identified by the index of their
+/// source file, which is also the `Span::file` of everything parsed from it.
+#[derive(Default)]
+struct Namespace {
+    functions: HashMap<String, Rc<Function>>,
+    structs: HashMap<String, Struct>,
+    globals: HashMap<String, GlobalSlot>,
+    imports: HashMap<String, usize>,
+}
 
 pub struct Interpeter {
-    functions: HashMap<String, Function>,
+    modules: HashMap<usize, Namespace>,
+    /// The module loaded last, which imports all the others.
+    entry: usize,
 }
 
 impl Interpeter {
     pub fn new() -> Self {
         Self {
-            functions: HashMap::new(),
+            modules: HashMap::new(),
+            entry: 0,
         }
     }
 
-    pub fn call_function(&mut self, function_name: &str) -> Value {
-        let main_function = self
-            .functions
-            .get(function_name)
-            .expect("Could not find function");
-        let mut env = Environment::new();
-        let main_arguments = Vec::new();
-        self.interpret_function(main_function, main_arguments, &mut env)
+    fn namespace(&self, module: usize) -> &Namespace {
+        &self.modules[&module]
     }
 
-    pub fn load(&mut self, program: Program) {
-        for global in program.globals {
+    pub fn call_function(&mut self, function_name: &str) -> Result<Value, SaltError> {
+        let main_function = match self.namespace(self.entry).functions.get(function_name) {
+            Some(function) => function,
+            None => {
+                return Err(SaltError::new(
+                    ErrorKind::Runtime,
+                    format!("Could not find function {function_name}"),
+                    None,
+                ))
+            }
+        };
+        let span = main_function.span;
+        let callee = Value::Function(Rc::new(Closure {
+            function: Callable::Tree(Rc::clone(main_function)),
+            captured: Vec::new(),
+        }));
+        self.call(EvaluatedCall {
+            callee,
+            arguments: Vec::new(),
+            span,
+            callee_span: span,
+        })
+    }
+
+    /// Registers the definitions in `module`, then evaluates its global
+    /// variables in order, so initializers can call any function but only
+    /// see the globals declared before them. The modules it imports must
+    /// already be loaded.
+    pub fn load(&mut self, module: Module) -> Result<(), SaltError> {
+        let mut namespace = Namespace {
+            imports: module.imports,
+            ..Namespace::default()
+        };
+        let mut variables = Vec::new();
+        for global in module.program.globals {
             match global {
                 Global::Function(function) => {
-                    self.functions.insert(function.name.clone(), function)
+                    namespace
+                        .functions
+                        .insert(function.name.clone(), Rc::new(function));
+                }
+                Global::Struct(definition) => {
+                    namespace
+                        .structs
+                        .insert(definition.name.clone(), definition);
                 }
+                // Enum literals and patterns are validated by the checker
+                // before execution, so the definitions are not needed here.
+                Global::Enum(_) => (),
+                Global::Variable(variable) => variables.push(variable),
+                // Imports were resolved by the loader.
+                Global::Import(_) => (),
+            }
+        }
+        self.modules.insert(module.file, namespace);
+        self.entry = module.file;
+        for variable in variables {
+            let mut env = Environment::new(module.file, variable.slots);
+            let value = self.interpret_expression(&variable.expr, &mut env)?;
+            let slot = GlobalSlot {
+                value: RefCell::new(value),
             };
+            let namespace = self.modules.get_mut(&module.file).unwrap();
+            namespace.globals.insert(variable.name, slot);
         }
+        Ok(())
     }
 
-    fn interpret_function(
-        &self,
-        function: &Function,
-        arguments: Vec<Value>,
-        env: &mut Environment,
-    ) -> Value {
-        //println!("<Interpreting {:?} with {:?} = {:?}>", function.name, function.parameters, arguments);
-        if function.parameters.len() != arguments.len() {
-            panic!("Invalid arity!");
-        }
-        for (parameter, argument) in function.parameters.iter().zip(arguments) {
-            env.set(parameter.to_string(), argument);
+    fn check_arity(function: &Function, arguments: &[Value], span: Span) -> Result<(), SaltError> {
+        ops::check_arity(
+            &function.name,
+            function.parameters.len(),
+            arguments.len(),
+            span,
+            function.span,
+        )
+    }
+
+    /// Makes `call`, then each tail call it returns in turn, reusing this
+    /// Rust stack frame.
+    fn call(&self, mut call: EvaluatedCall) -> Result<Value, SaltError> {
+        loop {
+            let closure = match call.callee {
+                Value::Function(closure) => closure,
+                Value::Builtin(name) => {
+                    return builtins::call(name, call.arguments, call.span)
+                        .expect("builtin values name existing builtins")
+                }
+                other => return Err(ops::not_callable(&other, call.callee_span)),
+            };
+            let Callable::Tree(function) = &closure.function else {
+                unreachable!("the interpreter only creates syntax tree functions");
+            };
+            Self::check_arity(function, &call.arguments, call.span)?;
+            let mut env = Environment::new(function.span.file, function.slots);
+            for (capture, value) in function.captures.iter().zip(&closure.captured) {
+                env.set(capture.to, value.clone());
+            }
+            // Parameters occupy the first slots.
+            for (slot, argument) in call.arguments.into_iter().enumerate() {
+                env.set(slot, argument);
+            }
+            call = match self.interpret_block(&function.block, &mut env)? {
+                ControlFlow::TailCall(tail_call) => tail_call,
+                ControlFlow::Return(value) => return Ok(value),
+                _ => return Ok(Value::Unit),
+            };
         }
-        self.interpret_block(&function.block, env)
-            .unwrap_or(Value::Unit)
     }
 
-    fn interpret_statement(&self, statement: &Statement, env: &mut Environment) -> Option<Value> {
+    fn interpret_statement(
+        &self,
+        statement: &Statement,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
         match statement {
+            Statement::Let(let_stmt) => self.interpret_let(let_stmt, env),
             Statement::If(if_stmt) => self.interpret_if(if_stmt, env),
             Statement::While(while_loop) => self.interpret_while(while_loop, env),
+            Statement::For(for_loop) => self.interpret_for(for_loop, env),
             Statement::Return(return_stmt) => self.interpret_return(return_stmt, env),
             Statement::Expr(expr) => {
-                self.interpret_expression(expr, env);
-                None
-            },
+                self.interpret_expression(expr, env)?;
+                Ok(ControlFlow::Normal)
+            }
             Statement::Assignment(assignment) => self.interpret_assignment(assignment, env),
             Statement::Print(print) => self.interpret_print(print, env),
+            Statement::Break(_) => Ok(ControlFlow::Break),
+            Statement::Continue(_) => Ok(ControlFlow::Continue),
+            Statement::Match(match_stmt) => {
+                self.interpret_match(match_stmt, env, Self::interpret_block)
+            }
         }
     }
 
-    fn interpret_if(&self, if_stmt: &IfStmt, env: &mut Environment) -> Option<Value> {
-        let condition = self.interpret_expression(&if_stmt.condition, env);
-        match condition {
-            Value::Boolean(true) => (),
-            Value::Boolean(false) => return None,
-            _ => panic!("Type error"),
+    fn interpret_let(
+        &self,
+        let_stmt: &Let,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        let evaluated = self.interpret_expression(&let_stmt.expr, env)?;
+        env.set(let_stmt.slot, evaluated);
+        Ok(ControlFlow::Normal)
+    }
+
+    fn interpret_if(
+        &self,
+        if_stmt: &IfStmt,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        if self.interpret_condition(&if_stmt.condition, env)? {
+            return self.interpret_block(&if_stmt.body, env);
+        }
+        match &if_stmt.else_branch {
+            Some(Else::Block(block)) => self.interpret_block(block, env),
+            Some(Else::If(else_if)) => self.interpret_if(else_if, env),
+            None => Ok(ControlFlow::Normal),
         }
-        self.interpret_block(&if_stmt.body, env)
     }
 
-    fn interpret_while(&self, while_loop: &WhileLoop, env: &mut Environment) -> Option<Value> {
-        loop {
-            let condition = self.interpret_expression(&while_loop.condition, env);
-            match condition {
-                Value::Boolean(true) => (),
-                Value::Boolean(false) => return None,
-                _ => panic!("Type error"),
+    fn interpret_while(
+        &self,
+        while_loop: &WhileLoop,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        while self.interpret_condition(&while_loop.condition, env)? {
+            match self.interpret_block(&while_loop.body, env)? {
+                ControlFlow::Normal | ControlFlow::Continue => (),
+                ControlFlow::Break => break,
+                control_flow => return Ok(control_flow),
             }
-            if let Some(value) = self.interpret_block(&while_loop.body, env) {
-                return Some(value);
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    fn interpret_for(
+        &self,
+        for_loop: &ForLoop,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        let range = match &for_loop.iterable {
+            Iterable::Range(range) => range,
+            Iterable::Expr(expr) => return self.interpret_for_each(for_loop, expr, env),
+        };
+        let start = ops::range_bound(
+            self.interpret_expression(&range.start, env)?,
+            range.start.span(),
+        )?;
+        let end = ops::range_bound(
+            self.interpret_expression(&range.end, env)?,
+            range.end.span(),
+        )?;
+        let step = match &range.step {
+            Some(step) => ops::range_step(self.interpret_expression(step, env)?, step.span())?,
+            None => 1,
+        };
+        for i in RangeIter::new(start, end, step, range.inclusive) {
+            match self.interpret_for_body(for_loop, Value::Integer(i), env)? {
+                ControlFlow::Normal | ControlFlow::Continue => (),
+                ControlFlow::Break => break,
+                control_flow => return Ok(control_flow),
             }
         }
+        Ok(ControlFlow::Normal)
     }
 
-    fn interpret_block(&self, block: &Block, env: &mut Environment) -> Option<Value> {
+    /// Iterates over the elements of an array or the keys of a map, in
+    /// order. The loop sees a snapshot taken before the first iteration, so
+    /// the body may modify the collection.
+    fn interpret_for_each(
+        &self,
+        for_loop: &ForLoop,
+        expr: &Expr,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        let items = ops::iteration_items(self.interpret_expression(expr, env)?, expr.span())?;
+        for item in items {
+            match self.interpret_for_body(for_loop, item, env)? {
+                ControlFlow::Normal | ControlFlow::Continue => (),
+                ControlFlow::Break => break,
+                control_flow => return Ok(control_flow),
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Runs one iteration of a `for` loop with `item` in the loop variable.
+    fn interpret_for_body(
+        &self,
+        for_loop: &ForLoop,
+        item: Value,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        env.set(for_loop.slot, item);
+        self.interpret_block(&for_loop.body, env)
+    }
+
+    fn interpret_condition(
+        &self,
+        condition: &Expr,
+        env: &mut Environment,
+    ) -> Result<bool, SaltError> {
+        ops::condition(self.interpret_expression(condition, env)?, condition.span())
+    }
+
+    fn interpret_block(
+        &self,
+        block: &Block,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
         for statement in &block.statements {
-            if let Some(value) = self.interpret_statement(statement, env) {
-                return Some(value);
+            match self.interpret_statement(statement, env)? {
+                ControlFlow::Normal => (),
+                control_flow => return Ok(control_flow),
             }
         }
-        None
+        Ok(ControlFlow::Normal)
     }
 
-    fn interpret_return(&self, return_stmt: &Return, env: &Environment) -> Option<Value> {
-        let evaluated = self.interpret_expression(&return_stmt.expr, env);
-        Some(evaluated)
+    fn interpret_return(
+        &self,
+        return_stmt: &Return,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        self.interpret_tail(&return_stmt.expr, env)
     }
 
-    fn interpret_assignment(&self, assignment: &Assignment, env: &mut Environment) -> Option<Value> {
-        let evaluated = self.interpret_expression(&assignment.expr, env);
-        env.set(assignment.name.clone(), evaluated);
-        None
+    /// Evaluates a returned expression, leaving the calls in tail position,
+    /// including those in the arms of a match, to the caller.
+    fn interpret_tail(&self, expr: &Expr, env: &mut Environment) -> Result<ControlFlow, SaltError> {
+        match expr {
+            Expr::Call(call) => Ok(ControlFlow::TailCall(self.evaluate_call(call, env)?)),
+            Expr::Match(match_expr) => self.interpret_match(match_expr, env, Self::interpret_tail),
+            expr => Ok(ControlFlow::Return(self.interpret_expression(expr, env)?)),
+        }
     }
 
-    fn interpret_print(&self, print: &Print, env: &mut Environment) -> Option<Value> {
-        let evaluated = self.interpret_expression(&print.expr, env);
-        match evaluated {
-            Value::Unit => println!("()"),
-            Value::Boolean(v) => println!("{v}"),
-            Value::Integer(v) => println!("{v}"),
+    /// Assigns to the local variable `name` resolves to, or else to the
+    /// global variable of that name.
+    fn assign_name(&self, name: &Name, value: Value, env: &mut Environment) {
+        if let Some(slot) = name.slot() {
+            env.set(slot, value);
+            return;
         }
-        None
+        self.assign_global(env.module, &name.name, value)
+            .expect("the resolver only resolves assignments to declared globals");
+    }
+
+    /// Assigns to the global variable `name` of `module`. Returns `None` if
+    /// there is no such global. The resolver reports assignments to
+    /// constants.
+    fn assign_global(&self, module: usize, name: &str, value: Value) -> Option<()> {
+        let slot = self.namespace(module).globals.get(name)?;
+        *slot.value.borrow_mut() = value;
+        Some(())
     }
 
-    fn interpret_expression(&self, expression: &Expr, env: &Environment) -> Value {
+    fn interpret_assignment(
+        &self,
+        assignment: &Assignment,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        match &assignment.target {
+            Target::Name(name) => {
+                let evaluated = self.interpret_expression(&assignment.expr, env)?;
+                self.assign_name(name, evaluated, env);
+            }
+            Target::Index(index) => {
+                let target = self.interpret_expression(&index.target, env)?;
+                let position = self.interpret_expression(&index.index, env)?;
+                let evaluated = self.interpret_expression(&assignment.expr, env)?;
+                ops::set_index(target, &position, evaluated, &IndexSpans::new(index))?;
+            }
+            Target::Field(field) => {
+                if let Some((module_name, module)) = self.imported_module(&field.target, env) {
+                    let evaluated = self.interpret_expression(&assignment.expr, env)?;
+                    return self
+                        .assign_global(module, &field.field.name, evaluated)
+                        .map(|()| ControlFlow::Normal)
+                        .ok_or_else(|| ops::unknown_member(module_name, &field.field));
+                }
+                let target = self.interpret_expression(&field.target, env)?;
+                let evaluated = self.interpret_expression(&assignment.expr, env)?;
+                let instance =
+                    ops::expect_instance(target, &field.field.name, field.target.span())?;
+                if !instance.set(&field.field.name, evaluated) {
+                    return Err(self.unknown_field(&instance.name, instance.module, &field.field));
+                }
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    fn interpret_print(
+        &self,
+        print: &Print,
+        env: &mut Environment,
+    ) -> Result<ControlFlow, SaltError> {
+        let evaluated = self.interpret_expression(&print.expr, env)?;
+        println!("{evaluated}");
+        Ok(ControlFlow::Normal)
+    }
+
+    fn interpret_expression(
+        &self,
+        expression: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, SaltError> {
         match expression {
-            Expr::Literal(v) => *v,
-            Expr::Call(call) => {
-                let function = self.functions.get(&call.name).expect("No such function");
-                let mut new_env = Environment::new();
-                let arguments_evaluated = call
-                    .arguments
+            Expr::Literal(literal) => Ok(literal.value.clone()),
+            Expr::Call(call) => self.interpret_call(call, env),
+            Expr::Array(array) => {
+                let elements = array
+                    .elements
                     .iter()
-                    .map(|expr| self.interpret_expression(expr, env))
-                    .collect();
-                self.interpret_function(function, arguments_evaluated, &mut new_env)
+                    .map(|element| self.interpret_expression(element, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::array(elements))
+            }
+            Expr::Map(map_literal) => {
+                let mut map = Map::new();
+                for (key, value) in &map_literal.entries {
+                    let key_evaluated = self.interpret_expression(key, env)?;
+                    let key_evaluated = Key::from_value(&key_evaluated, key.span())?;
+                    map.insert(key_evaluated, self.interpret_expression(value, env)?);
+                }
+                Ok(Value::map(map))
+            }
+            Expr::StructLiteral(literal) => self.interpret_struct_literal(literal, env),
+            Expr::Index(index) => self.interpret_index(index, env),
+            Expr::Field(field) => {
+                if let Some((module_name, module)) = self.imported_module(&field.target, env) {
+                    return self
+                        .lookup_global(module, &field.field.name)
+                        .ok_or_else(|| ops::unknown_member(module_name, &field.field));
+                }
+                let target = self.interpret_expression(&field.target, env)?;
+                let instance =
+                    ops::expect_instance(target, &field.field.name, field.target.span())?;
+                instance.get(&field.field.name).ok_or_else(|| {
+                    self.unknown_field(&instance.name, instance.module, &field.field)
+                })
             }
             Expr::UnaryOp(unary_op) => self.interpret_unary_op(unary_op, env),
             Expr::BinaryOp(binary_op) => self.interpret_binary_op(binary_op, env),
-            Expr::Name(name) => {
-                let evaluated = env.get(name).expect("No such variable");
-                *evaluated
-            }
+            // Only a global whose initializer has not run yet can be missing.
+            Expr::Name(name) => self
+                .lookup(name, env)
+                .ok_or_else(|| ops::no_such_variable(name)),
+            Expr::Lambda(lambda) => Ok(Value::Function(Rc::new(Closure {
+                function: Callable::Tree(lambda.function.clone()),
+                captured: lambda
+                    .function
+                    .captures
+                    .iter()
+                    .map(|capture| env.get(capture.from).clone())
+                    .collect(),
+            }))),
             Expr::Time(time) => self.interpret_time(time, env),
+            Expr::EnumLiteral(literal) => {
+                let fields = literal
+                    .arguments
+                    .iter()
+                    .map(|expr| self.interpret_expression(expr, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Enum(Rc::new(EnumValue {
+                    enum_name: literal.enum_name.clone(),
+                    module: self.type_module(literal.module.as_ref(), env.module),
+                    variant: literal.variant.name.clone(),
+                    fields,
+                })))
+            }
+            Expr::Match(match_expr) => {
+                self.interpret_match(match_expr, env, Self::interpret_expression)
+            }
+        }
+    }
+
+    /// Runs the body of the first arm whose pattern matches the scrutinee,
+    /// with the pattern's bindings stored in their slots.
+    fn interpret_match<T, R>(
+        &self,
+        match_: &Match<T>,
+        env: &mut Environment,
+        interpret_body: fn(&Self, &T, &mut Environment) -> Result<R, SaltError>,
+    ) -> Result<R, SaltError> {
+        let scrutinee = self.interpret_expression(&match_.scrutinee, env)?;
+        for arm in &match_.arms {
+            let mut bindings = Vec::new();
+            if self.match_pattern(&arm.pattern, &scrutinee, env.module, &mut bindings) {
+                for (slot, value) in bindings {
+                    env.set(slot, value);
+                }
+                return interpret_body(self, &arm.body, env);
+            }
+        }
+        Err(ops::no_match(&scrutinee, env.module, match_.span))
+    }
+
+    /// Matches `value` against `pattern`, written in `module`.
+    fn match_pattern(
+        &self,
+        pattern: &Pattern,
+        value: &Value,
+        module: usize,
+        bindings: &mut Vec<(usize, Value)>,
+    ) -> bool {
+        match (pattern, value) {
+            (Pattern::Wildcard(_), _) => true,
+            (Pattern::Binding(name), value) => {
+                let slot = name.slot().expect("bindings are resolved to slots");
+                bindings.push((slot, value.clone()));
+                true
+            }
+            (Pattern::Literal(literal), value) => ops::equal(&literal.value, value),
+            (Pattern::Variant(pattern), Value::Enum(value)) => {
+                pattern.enum_name == value.enum_name
+                    && self.type_module(pattern.module.as_ref(), module) == value.module
+                    && pattern.variant.name == value.variant
+                    && pattern.fields.len() == value.fields.len()
+                    && pattern
+                        .fields
+                        .iter()
+                        .zip(&value.fields)
+                        .all(|(pattern, value)| {
+                            self.match_pattern(pattern, value, module, bindings)
+                        })
+            }
+            (Pattern::Variant(_), _) => false,
+        }
+    }
+
+    fn interpret_struct_literal(
+        &self,
+        literal: &StructLiteral,
+        env: &mut Environment,
+    ) -> Result<Value, SaltError> {
+        let module = self.type_module(literal.module.as_ref(), env.module);
+        let Some(definition) = self.namespace(module).structs.get(&literal.name) else {
+            return Err(ops::no_such_struct(literal));
+        };
+        let mut values: Vec<Option<Value>> = vec![None; definition.fields.len()];
+        for (field, expr) in &literal.fields {
+            let Some(i) = definition.fields.iter().position(|f| *f == field.name) else {
+                return Err(self.unknown_field(&definition.name, module, field));
+            };
+            if values[i].is_some() {
+                return Err(SaltError::runtime(
+                    format!("Field `{}` is specified more than once", field.name),
+                    field.span,
+                ));
+            }
+            values[i] = Some(self.interpret_expression(expr, env)?);
+        }
+        let fields = definition
+            .fields
+            .iter()
+            .zip(values)
+            .map(|(name, value)| match value {
+                Some(value) => Ok((name.clone(), value)),
+                None => Err(SaltError::runtime(
+                    format!("Missing field `{name}` in `{}` literal", definition.name),
+                    literal.span,
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::Struct(Rc::new(Instance::new(
+            definition.name.clone(),
+            module,
+            fields,
+        ))))
+    }
+
+    /// Reports that the struct `struct_name` of `module` has no such field.
+    fn unknown_field(&self, struct_name: &str, module: usize, field: &Name) -> SaltError {
+        let definition = self.namespace(module).structs.get(struct_name);
+        ops::unknown_field(
+            struct_name,
+            &field.name,
+            field.span,
+            definition.map(|definition| definition.span),
+        )
+    }
+
+    fn interpret_index(&self, index: &Index, env: &mut Environment) -> Result<Value, SaltError> {
+        let target = self.interpret_expression(&index.target, env)?;
+        let position = self.interpret_expression(&index.index, env)?;
+        ops::index(target, &position, &IndexSpans::new(index))
+    }
+
+    /// Looks up the local variable `name` resolves to, or else a global
+    /// variable or function of the current module, or a builtin.
+    fn lookup(&self, name: &Name, env: &Environment) -> Option<Value> {
+        if let Some(slot) = name.slot() {
+            return Some(env.get(slot).clone());
+        }
+        self.lookup_global(env.module, &name.name)
+            .or_else(|| builtins::lookup(&name.name))
+    }
+
+    /// Resolves a name to a global variable or function of `module`.
+    fn lookup_global(&self, module: usize, name: &str) -> Option<Value> {
+        let namespace = self.namespace(module);
+        if let Some(slot) = namespace.globals.get(name) {
+            return Some(slot.value.borrow().clone());
+        }
+        let function = namespace.functions.get(name)?;
+        Some(Value::Function(Rc::new(Closure {
+            function: Callable::Tree(function.clone()),
+            captured: Vec::new(),
+        })))
+    }
+
+    /// The module defining a struct or enum qualified with `module`, as
+    /// written in the module `current`. The checker made sure that a
+    /// qualified type names an import.
+    fn type_module(&self, module: Option<&Name>, current: usize) -> usize {
+        match module {
+            Some(module) => self.namespace(current).imports[&module.name],
+            None => current,
+        }
+    }
+
+    fn imported_module<'e>(&self, target: &'e Expr, env: &Environment) -> Option<(&'e str, usize)> {
+        ops::imported_module(target, &self.namespace(env.module).imports)
+    }
+
+    fn interpret_call(&self, call: &Call, env: &mut Environment) -> Result<Value, SaltError> {
+        self.call(self.evaluate_call(call, env)?)
+    }
+
+    fn evaluate_call(
+        &self,
+        call: &Call,
+        env: &mut Environment,
+    ) -> Result<EvaluatedCall, SaltError> {
+        let callee = match call.callee.as_ref() {
+            Expr::Name(name) => self
+                .lookup(name, env)
+                .ok_or_else(|| ops::no_such_function(name, call.span))?,
+            callee => self.interpret_expression(callee, env)?,
+        };
+        let arguments = call
+            .arguments
+            .iter()
+            .map(|expr| self.interpret_expression(expr, env))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(EvaluatedCall {
+            callee,
+            arguments,
+            span: call.span,
+            callee_span: call.callee.span(),
+        })
+    }
+
+    fn interpret_unary_op(&self, op: &UnaryOp, env: &mut Environment) -> Result<Value, SaltError> {
+        let expr_evaluated = self.interpret_expression(&op.expr, env)?;
+        ops::unary(op.op_type, expr_evaluated, op.span)
+    }
+
+    fn interpret_binary_op(
+        &self,
+        op: &BinaryOp,
+        env: &mut Environment,
+    ) -> Result<Value, SaltError> {
+        if let BinaryOpType::And | BinaryOpType::Or = op.op_type {
+            return self.interpret_logical_op(op, env);
+        }
+        let left_evaluated = self.interpret_expression(&op.left, env)?;
+        let right_evaluated = self.interpret_expression(&op.right, env)?;
+        ops::binary(op.op_type, left_evaluated, right_evaluated, op.span)
+    }
+
+    /// Evaluates `&&` and `||`, only evaluating the right operand when the
+    /// left one does not already decide the result.
+    fn interpret_logical_op(
+        &self,
+        op: &BinaryOp,
+        env: &mut Environment,
+    ) -> Result<Value, SaltError> {
+        let short_circuit = matches!(op.op_type, BinaryOpType::Or);
+        for operand in [&op.left, &op.right] {
+            let evaluated = self.interpret_expression(operand, env)?;
+            if ops::logical_operand(op.op_type, evaluated, operand.span())? == short_circuit {
+                return Ok(Value::Boolean(short_circuit));
+            }
         }
+        Ok(Value::Boolean(!short_circuit))
     }
 
-    fn interpret_unary_op(&self, op: &UnaryOp, env: &Environment) -> Value {
-        let expr_evaluated = self.interpret_expression(&op.expr, env);
-        match op.op_type {
-            UnaryOpType::Negate => match expr_evaluated {
-                Value::Integer(v) => Value::Integer(-v),
-                _ => panic!("Type error"),
-            },
-        }
-    }
-
-    fn interpret_binary_op(&self, op: &BinaryOp, env: &Environment) -> Value {
-        let left_evaluated = self.interpret_expression(&op.left, env);
-        let right_evaluated = self.interpret_expression(&op.right, env);
-        match op.op_type {
-            BinaryOpType::Addition => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Integer(l + r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::Subtraction => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Integer(l - r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::Multiplication => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Integer(l * r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::Division => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Integer(l / r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::Modulo => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Integer(l % r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::EqualTo => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l == r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::NotEqualTo => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l != r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::LessThan => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l < r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::LessThanOrEqualTo => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l <= r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::GreaterThan => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l > r),
-                _ => panic!("Type error"),
-            },
-            BinaryOpType::GreaterThanOrEqualTo => match (left_evaluated, right_evaluated) {
-                (Value::Integer(l), Value::Integer(r)) => Value::Boolean(l >= r),
-                _ => panic!("Type error"),
-            },
-        }
-    }
-
-    fn interpret_time(&self, _time: &Time, _env: &Environment) -> Value {
-        let millis = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        Value::Integer(millis as i64)
+    fn interpret_time(&self, _time: &Time, _env: &mut Environment) -> Result<Value, SaltError> {
+        Ok(ops::time())
     }
 }
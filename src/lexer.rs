@@ -1,56 +1,108 @@
 use std::iter::Peekable;
 use std::str::Chars;
 
-use crate::token::Token;
+use crate::bigint::BigInt;
+use crate::error::SaltError;
+use crate::span::Span;
+use crate::token::{SpannedToken, Token};
 
 pub struct Lexer<'a> {
     chars: Peekable<Chars<'a>>,
+    offset: usize,
+    line: usize,
+    column: usize,
+    file: usize,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(chars: Chars<'a>) -> Self {
         Self {
             chars: chars.peekable(),
+            offset: 0,
+            line: 1,
+            column: 1,
+            file: 0,
         }
     }
 
-    pub fn lex(&mut self) -> Vec<Token> {
+    /// Marks the spans of the lexed tokens as belonging to source file `file`.
+    pub fn with_file(mut self, file: usize) -> Self {
+        self.file = file;
+        self
+    }
+
+    pub fn lex(&mut self) -> Result<Vec<SpannedToken>, SaltError> {
         let mut tokens = Vec::new();
         loop {
-            match self.next_token() {
-                Some(Token::End) => break,
-                token => tokens.extend(token),
+            let start = self.position();
+            let token = self.next_token()?;
+            let span = Span {
+                length: self.offset - start.offset,
+                ..start
+            };
+            match token {
+                Some(Token::End) => {
+                    tokens.push(SpannedToken {
+                        token: Token::End,
+                        span,
+                    });
+                    break;
+                }
+                Some(token) => tokens.push(SpannedToken { token, span }),
+                None => (),
             }
         }
-        tokens
+        Ok(tokens)
+    }
+
+    fn position(&self) -> Span {
+        Span {
+            file: self.file,
+            ..Span::new(self.offset, self.line, self.column, 0)
+        }
     }
 
-    fn next_token(&mut self) -> Option<Token> {
-        match self.chars.peek() {
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        self.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, SaltError> {
+        let token = match self.chars.peek() {
             Some(ch) => match ch {
                 ch if ch.is_whitespace() => {
-                    self.chars.next();
+                    self.bump();
                     None
                 }
                 '#' => {
                     loop {
-                        match self.chars.next() {
+                        match self.bump() {
                             Some('\n') | None => break,
                             _ => (),
                         }
                     }
                     None
                 }
-                ch if ch.is_ascii_alphabetic() => Some(self.next_name_or_keyword()),
-                ch if ch.is_ascii_digit() => Some(self.next_integer()),
-                _ => Some(self.next_symbol()),
+                ch if ch.is_ascii_alphabetic() || *ch == '_' => Some(self.next_name_or_keyword()),
+                ch if ch.is_ascii_digit() => Some(self.next_number()?),
+                '"' => Some(self.next_string()?),
+                _ => Some(self.next_symbol()?),
             },
             None => Some(Token::End),
-        }
+        };
+        Ok(token)
     }
 
-    fn next_symbol(&mut self) -> Token {
-        match self.chars.next() {
+    fn next_symbol(&mut self) -> Result<Token, SaltError> {
+        let start = self.position();
+        let token = match self.bump() {
             Some(ch) => match ch {
                 '+' => Token::Plus,
                 '-' => Token::Minus,
@@ -61,56 +113,109 @@ impl<'a> Lexer<'a> {
                 ')' => Token::RParen,
                 '{' => Token::LCurly,
                 '}' => Token::RCurly,
+                '[' => Token::LSquare,
+                ']' => Token::RSquare,
                 ';' => Token::Semicolon,
                 ',' => Token::Comma,
+                ':' => match self.chars.peek() {
+                    Some(':') => {
+                        self.bump();
+                        Token::ColonColon
+                    }
+                    _ => Token::Colon,
+                },
                 '=' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.bump();
                         Token::DoubleEqual
                     }
+                    Some('>') => {
+                        self.bump();
+                        Token::FatArrow
+                    }
                     _ => Token::Equal,
                 },
                 '!' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.bump();
                         Token::NotEqual
                     }
                     _ => Token::Bang,
                 },
+                '.' => match self.chars.peek() {
+                    Some('.') => {
+                        self.bump();
+                        match self.chars.peek() {
+                            Some('=') => {
+                                self.bump();
+                                Token::DotDotEqual
+                            }
+                            _ => Token::DotDot,
+                        }
+                    }
+                    _ => Token::Dot,
+                },
+                '&' if self.chars.peek() == Some(&'&') => {
+                    self.bump();
+                    Token::DoubleAmpersand
+                }
+                '|' if self.chars.peek() == Some(&'|') => {
+                    self.bump();
+                    Token::DoublePipe
+                }
                 '<' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.bump();
                         Token::LessEqual
                     }
                     _ => Token::Less,
                 },
                 '>' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.bump();
                         Token::GreaterEqual
                     }
                     _ => Token::Greater,
                 },
-                _ => panic!("Unknown symbol: {ch:?}"),
+                _ => {
+                    let span = Span {
+                        length: ch.len_utf8(),
+                        ..start
+                    };
+                    return Err(SaltError::lex(format!("Unknown symbol: {ch:?}"), span));
+                }
             },
-            None => panic!("Internal Error"),
-        }
+            None => return Err(SaltError::lex("Unexpected end of input", start)),
+        };
+        Ok(token)
     }
 
     fn next_name_or_keyword(&mut self) -> Token {
         let mut name = String::new();
-        while let Some(ch) = self.chars.peek() {
-            if !ch.is_ascii_alphabetic() && *ch != '_' {
+        while let Some(&ch) = self.chars.peek() {
+            if !ch.is_ascii_alphabetic() && ch != '_' {
                 break;
             }
-            name.push(*ch);
-            self.chars.next();
+            name.push(ch);
+            self.bump();
         }
         match name.as_str() {
             "fn" => Token::Function,
+            "struct" => Token::Struct,
+            "enum" => Token::Enum,
+            "import" => Token::Import,
+            "const" => Token::Const,
+            "global" => Token::Global,
+            "match" => Token::Match,
+            "let" => Token::Let,
             "if" => Token::If,
+            "else" => Token::Else,
             "while" => Token::While,
+            "for" => Token::For,
+            "in" => Token::In,
             "return" => Token::Return,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
             "print" => Token::Print,
             "time" => Token::Time,
             "true" => Token::True,
@@ -119,16 +224,128 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn next_integer(&mut self) -> Token {
+    fn next_string(&mut self) -> Result<Token, SaltError> {
+        let start = self.position();
+        self.bump();
+        let mut string = String::new();
+        loop {
+            let escape_start = self.position();
+            match self.bump() {
+                Some('"') => return Ok(Token::String(string)),
+                Some('\\') => string.push(self.next_escape(escape_start)?),
+                Some(ch) => string.push(ch),
+                None => {
+                    let span = Span {
+                        length: self.offset - start.offset,
+                        ..start
+                    };
+                    return Err(SaltError::lex("Unterminated string literal", span));
+                }
+            }
+        }
+    }
+
+    /// Decodes the escape sequence following a backslash at `start`.
+    fn next_escape(&mut self, start: Span) -> Result<char, SaltError> {
+        let ch = match self.bump() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('0') => Some('\0'),
+            Some('"') => Some('"'),
+            Some('\\') => Some('\\'),
+            Some('u') => self.next_unicode_escape(),
+            _ => None,
+        };
+        ch.ok_or_else(|| {
+            let span = Span {
+                length: self.offset - start.offset,
+                ..start
+            };
+            SaltError::lex("Invalid escape sequence", span)
+        })
+    }
+
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn next_unicode_escape(&mut self) -> Option<char> {
+        if self.chars.peek() != Some(&'{') {
+            return None;
+        }
+        self.bump();
+        let mut digits = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !ch.is_ascii_hexdigit() {
+                break;
+            }
+            digits.push(ch);
+            self.bump();
+        }
+        if self.bump() != Some('}') || digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+
+    fn next_number(&mut self) -> Result<Token, SaltError> {
+        let start = self.position();
         let mut buffer = String::new();
-        while let Some(ch) = self.chars.peek() {
+        self.next_digits(&mut buffer);
+        let mut is_float = false;
+        // A `.` only starts a fraction when a digit follows, so that `1..2`
+        // still lexes as a range.
+        if self.chars.peek() == Some(&'.') && self.second_char_is(|ch| ch.is_ascii_digit()) {
+            is_float = true;
+            buffer.push('.');
+            self.bump();
+            self.next_digits(&mut buffer);
+        }
+        if matches!(self.chars.peek(), Some('e' | 'E'))
+            && self.second_char_is(|ch| ch.is_ascii_digit() || ch == '-' || ch == '+')
+        {
+            is_float = true;
+            buffer.push('e');
+            self.bump();
+            if let Some(&sign @ ('-' | '+')) = self.chars.peek() {
+                buffer.push(sign);
+                self.bump();
+            }
+            self.next_digits(&mut buffer);
+        }
+        let span = Span {
+            length: self.offset - start.offset,
+            ..start
+        };
+        if is_float {
+            return match buffer.parse() {
+                Ok(f) => Ok(Token::Float(f)),
+                Err(_) => Err(SaltError::lex("Invalid float literal", span)),
+            };
+        }
+        match buffer.parse() {
+            Ok(i) => Ok(Token::Integer(i)),
+            Err(_) => match BigInt::parse(&buffer) {
+                Some(i) => Ok(Token::BigInteger(i)),
+                None => Err(SaltError::lex("Invalid integer literal", span)),
+            },
+        }
+    }
+
+    fn next_digits(&mut self, buffer: &mut String) {
+        while let Some(&ch) = self.chars.peek() {
             if !ch.is_ascii_digit() {
                 break;
             }
-            buffer.push(*ch);
-            self.chars.next();
+            buffer.push(ch);
+            self.bump();
         }
-        let i = buffer.parse().unwrap();
-        Token::Integer(i)
+    }
+
+    /// Checks the character after the next one without consuming anything.
+    fn second_char_is(&self, predicate: impl Fn(char) -> bool) -> bool {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.peek().is_some_and(|&ch| predicate(ch))
     }
 }
@@ -1,22 +1,54 @@
 mod ast;
+mod bigint;
+mod builtins;
+mod bytecode;
+mod checker;
+mod compiler;
+mod diagnostic;
 mod environment;
+mod error;
 mod interpreter;
 mod lexer;
+mod module;
+mod ops;
+mod optimizer;
 mod parser;
+mod resolver;
 mod salt;
+mod source;
+mod span;
 mod tests;
 mod token;
 mod value;
+mod vm;
 
-use salt::Salt;
+use module::Loader;
+use salt::{Backend, Salt};
 
-use std::{env, fs};
+use std::{env, path::Path, process};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_name = args.get(1).expect("usage: salt <filename>");
-    let source = fs::read_to_string(file_name).expect("Unable to read file!");
+    let mut salt = Salt::new();
+    let mut file_name = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--vm" => salt = salt.with_backend(Backend::Vm),
+            "-O" => salt = salt.with_optimizer(true),
+            _ => file_name = Some(arg),
+        }
+    }
+    let file_name = file_name.expect("usage: salt [--vm] [-O] <filename>");
 
-    let salt = Salt::new();
-    salt.run(&source);
+    let mut loader = Loader::new();
+    let result = loader
+        .load_file(Path::new(&file_name))
+        .and_then(|modules| salt.execute(modules).map_err(|error| vec![error]));
+    if let Err(errors) = result {
+        let rendered: Vec<String> = errors
+            .iter()
+            .map(|error| diagnostic::render(error, loader.files()))
+            .collect();
+        eprint!("{}", rendered.join("\n"));
+        process::exit(1);
+    }
 }
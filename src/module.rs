@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Global, Program};
+use crate::error::{ErrorKind, SaltError};
+use crate::salt::Salt;
+use crate::source::SourceFile;
+use crate::span::Span;
+
+/// A parsed source file, with the modules it imports by the name they are
+/// qualified with. Modules are identified by the index of their file.
+pub struct Module {
+    pub file: usize,
+    pub program: Program,
+    pub imports: HashMap<String, usize>,
+}
+
+/// Reads a program and every file it imports, resolving each `import`
+/// relative to the importing file. Every file is loaded once, however often
+/// it is imported.
+pub struct Loader {
+    files: Vec<SourceFile>,
+    /// The index of every file read so far, by canonical path.
+    indices: HashMap<PathBuf, usize>,
+    /// The files whose imports are being loaded, innermost last.
+    loading: Vec<usize>,
+    /// The loaded modules, each after the modules it imports.
+    modules: Vec<Module>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            indices: HashMap::new(),
+            loading: Vec::new(),
+            modules: Vec::new(),
+        }
+    }
+
+    /// The files read so far, for rendering diagnostics.
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Loads the program starting at the file `path`.
+    pub fn load_file(&mut self, path: &Path) -> Result<Vec<Module>, Vec<SaltError>> {
+        let text = fs::read_to_string(path).map_err(|error| vec![read_error(path, error, None)])?;
+        self.load_source(path, text)
+    }
+
+    /// Loads the program starting with `source`, as if it was read from
+    /// `path`.
+    pub fn load_source(
+        &mut self,
+        path: &Path,
+        source: String,
+    ) -> Result<Vec<Module>, Vec<SaltError>> {
+        self.load_module(path, source)?;
+        Ok(std::mem::take(&mut self.modules))
+    }
+
+    fn load_module(&mut self, path: &Path, text: String) -> Result<usize, Vec<SaltError>> {
+        let file = self.files.len();
+        let (mut program, errors) = Salt::new().parse_file(&text, file);
+        self.files
+            .push(SourceFile::new(path.display().to_string(), text));
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.indices.insert(key, file);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.loading.push(file);
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut imports = HashMap::new();
+        for global in &program.globals {
+            let Global::Import(import) = global else {
+                continue;
+            };
+            let imported = self.load_import(&directory.join(&import.path), import.span)?;
+            if imports.insert(import.name.clone(), imported).is_some() {
+                return Err(vec![SaltError::new(
+                    ErrorKind::Import,
+                    format!("Module `{}` is imported more than once", import.name),
+                    Some(import.span),
+                )]);
+            }
+        }
+        self.loading.pop();
+
+        // Checking needs the types and globals of the imported modules.
+        let imported = imports
+            .iter()
+            .map(|(name, file)| (name.as_str(), &self.module(*file).program))
+            .collect();
+        let errors = Salt::new().check(&mut program, &imported);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.modules.push(Module {
+            file,
+            program,
+            imports,
+        });
+        Ok(file)
+    }
+
+    /// The loaded module of the file with index `file`.
+    fn module(&self, file: usize) -> &Module {
+        self.modules
+            .iter()
+            .find(|module| module.file == file)
+            .expect("imports are loaded before the modules importing them")
+    }
+
+    fn load_import(&mut self, path: &Path, span: Span) -> Result<usize, Vec<SaltError>> {
+        let key =
+            fs::canonicalize(path).map_err(|error| vec![read_error(path, error, Some(span))])?;
+        if let Some(&file) = self.indices.get(&key) {
+            if let Some(start) = self.loading.iter().position(|&loading| loading == file) {
+                let cycle: Vec<&str> = self.loading[start..]
+                    .iter()
+                    .chain([&file])
+                    .map(|&i| self.files[i].name.as_str())
+                    .collect();
+                return Err(vec![SaltError::new(
+                    ErrorKind::Import,
+                    format!("Import cycle: {}", cycle.join(" -> ")),
+                    Some(span),
+                )]);
+            }
+            return Ok(file);
+        }
+        let text =
+            fs::read_to_string(path).map_err(|error| vec![read_error(path, error, Some(span))])?;
+        self.load_module(path, text)
+    }
+}
+
+fn read_error(path: &Path, error: io::Error, span: Option<Span>) -> SaltError {
+    SaltError::new(
+        ErrorKind::Import,
+        format!("Cannot read `{}`: {error}", path.display()),
+        span,
+    )
+}
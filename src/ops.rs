@@ -0,0 +1,504 @@
+// The semantics of salt's operators, shared by the tree-walking interpreter
+// and the bytecode VM so that both compute the same values and report the
+// same errors. Callers pass the spans the errors should point at.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::{BinaryOpType, Expr, Index, Name, Resolution, StructLiteral, UnaryOpType};
+use crate::bigint::BigInt;
+use crate::error::SaltError;
+use crate::span::Span;
+use crate::value::{Instance, Key, Value};
+
+/// The spans of `target[index]`, for the errors of indexing.
+pub struct IndexSpans {
+    /// The whole expression.
+    pub whole: Span,
+    pub target: Span,
+    pub index: Span,
+}
+
+impl IndexSpans {
+    pub fn new(index: &Index) -> Self {
+        Self {
+            whole: index.span,
+            target: index.target.span(),
+            index: index.index.span(),
+        }
+    }
+}
+
+pub fn unary(op_type: UnaryOpType, value: Value, span: Span) -> Result<Value, SaltError> {
+    match (op_type, value) {
+        (UnaryOpType::Negate, Value::Integer(v)) => match v.checked_neg() {
+            Some(negated) => Ok(Value::Integer(negated)),
+            None => Ok(Value::from_bigint(BigInt::from_i64(v).neg())),
+        },
+        (UnaryOpType::Negate, Value::BigInt(v)) => Ok(Value::from_bigint(v.neg())),
+        (UnaryOpType::Negate, Value::Float(v)) => Ok(Value::Float(-v)),
+        (UnaryOpType::Not, Value::Boolean(v)) => Ok(Value::Boolean(!v)),
+        (op_type, v) => Err(SaltError::runtime(
+            format!("Type error: cannot apply {op_type:?} to {}", v.type_name()),
+            span,
+        )),
+    }
+}
+
+/// Applies a binary operator other than `&&` and `||`, which short-circuit
+/// and so are evaluated by the backends themselves. `+` concatenates two
+/// strings, or a string and a number, which is written as `print` writes it.
+pub fn binary(
+    op_type: BinaryOpType,
+    left: Value,
+    right: Value,
+    span: Span,
+) -> Result<Value, SaltError> {
+    let (left, right) = Value::promote(left, right);
+    let value = match (op_type, left, right) {
+        (
+            BinaryOpType::Addition
+            | BinaryOpType::Subtraction
+            | BinaryOpType::Multiplication
+            | BinaryOpType::Division
+            | BinaryOpType::Modulo,
+            l @ (Value::Integer(_) | Value::BigInt(_)),
+            r @ (Value::Integer(_) | Value::BigInt(_)),
+        ) => integer_arithmetic(op_type, &l, &r, span)?,
+        (BinaryOpType::Addition, Value::String(l), Value::String(r)) => {
+            Value::String(format!("{l}{r}").into())
+        }
+        (
+            BinaryOpType::Addition,
+            Value::String(l),
+            r @ (Value::Integer(_) | Value::BigInt(_) | Value::Float(_)),
+        ) => Value::String(format!("{l}{r}").into()),
+        (
+            BinaryOpType::Addition,
+            l @ (Value::Integer(_) | Value::BigInt(_) | Value::Float(_)),
+            Value::String(r),
+        ) => Value::String(format!("{l}{r}").into()),
+        (BinaryOpType::Addition, Value::Float(l), Value::Float(r)) => Value::Float(l + r),
+        (BinaryOpType::Subtraction, Value::Float(l), Value::Float(r)) => Value::Float(l - r),
+        (BinaryOpType::Multiplication, Value::Float(l), Value::Float(r)) => Value::Float(l * r),
+        (BinaryOpType::Division, Value::Float(l), Value::Float(r)) => Value::Float(l / r),
+        (BinaryOpType::Modulo, Value::Float(l), Value::Float(r)) => Value::Float(l % r),
+        (BinaryOpType::EqualTo, l, r) => Value::Boolean(l == r),
+        (BinaryOpType::NotEqualTo, l, r) => Value::Boolean(l != r),
+        (BinaryOpType::LessThan, l, r) => {
+            Value::Boolean(compare(&l, &r, span)?.is_some_and(Ordering::is_lt))
+        }
+        (BinaryOpType::LessThanOrEqualTo, l, r) => {
+            Value::Boolean(compare(&l, &r, span)?.is_some_and(Ordering::is_le))
+        }
+        (BinaryOpType::GreaterThan, l, r) => {
+            Value::Boolean(compare(&l, &r, span)?.is_some_and(Ordering::is_gt))
+        }
+        (BinaryOpType::GreaterThanOrEqualTo, l, r) => {
+            Value::Boolean(compare(&l, &r, span)?.is_some_and(Ordering::is_ge))
+        }
+        (op_type, l, r) => {
+            return Err(SaltError::runtime(
+                format!(
+                    "Type error: cannot apply {op_type:?} to {} and {}",
+                    l.type_name(),
+                    r.type_name()
+                ),
+                span,
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Integer arithmetic runs on `i64` while it can and falls back to
+/// arbitrary precision when a result overflows. Division by zero is a
+/// runtime error.
+fn integer_arithmetic(
+    op_type: BinaryOpType,
+    l: &Value,
+    r: &Value,
+    span: Span,
+) -> Result<Value, SaltError> {
+    if let (Value::Integer(l), Value::Integer(r)) = (l, r) {
+        let result = match op_type {
+            BinaryOpType::Addition => l.checked_add(*r),
+            BinaryOpType::Subtraction => l.checked_sub(*r),
+            BinaryOpType::Multiplication => l.checked_mul(*r),
+            BinaryOpType::Division => l.checked_div(*r),
+            BinaryOpType::Modulo => l.checked_rem(*r),
+            _ => unreachable!("{op_type:?} is not an arithmetic operator"),
+        };
+        if let Some(value) = result {
+            return Ok(Value::Integer(value));
+        }
+    }
+    let (l, r) = (
+        l.to_bigint().expect("operand is an Integer"),
+        r.to_bigint().expect("operand is an Integer"),
+    );
+    let result = match op_type {
+        BinaryOpType::Addition => l.add(&r),
+        BinaryOpType::Subtraction => l.sub(&r),
+        BinaryOpType::Multiplication => l.mul(&r),
+        BinaryOpType::Division | BinaryOpType::Modulo => {
+            let Some((quotient, remainder)) = l.div_rem(&r) else {
+                return Err(SaltError::runtime(format!("{op_type:?} by zero"), span));
+            };
+            match op_type {
+                BinaryOpType::Division => quotient,
+                _ => remainder,
+            }
+        }
+        _ => unreachable!("{op_type:?} is not an arithmetic operator"),
+    };
+    Ok(Value::from_bigint(result))
+}
+
+/// Orders two operands. Floats are unordered when one of them is NaN, in
+/// which case every comparison is false.
+fn compare(left: &Value, right: &Value, span: Span) -> Result<Option<Ordering>, SaltError> {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) => Ok(l.partial_cmp(r)),
+        _ => left.compare(right).map(Some).ok_or_else(|| {
+            SaltError::runtime(
+                format!(
+                    "Type error: cannot order {} and {}",
+                    left.type_name(),
+                    right.type_name()
+                ),
+                span,
+            )
+        }),
+    }
+}
+
+/// Checks that an operand of `&&` or `||` is a Boolean.
+pub fn logical_operand(op_type: BinaryOpType, value: Value, span: Span) -> Result<bool, SaltError> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        other => Err(SaltError::runtime(
+            format!(
+                "Type error: cannot apply {op_type:?} to {}",
+                other.type_name()
+            ),
+            span,
+        )),
+    }
+}
+
+/// Checks that the condition of an `if` or `while` is a Boolean.
+pub fn condition(value: Value, span: Span) -> Result<bool, SaltError> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        other => Err(SaltError::runtime(
+            format!(
+                "Type error: condition must be a Boolean, found {}",
+                other.type_name()
+            ),
+            span,
+        )),
+    }
+}
+
+/// Checks that the start or end of a range is an `i64`.
+pub fn range_bound(value: Value, span: Span) -> Result<i64, SaltError> {
+    match value {
+        Value::Integer(i) => Ok(i),
+        Value::BigInt(i) => Err(SaltError::runtime(
+            format!("range bound {i} does not fit in 64 bits"),
+            span,
+        )),
+        other => Err(SaltError::runtime(
+            format!(
+                "Type error: range bounds must be Integers, found {}",
+                other.type_name()
+            ),
+            span,
+        )),
+    }
+}
+
+/// Checks that the step of a range is a non-zero `i64`.
+pub fn range_step(value: Value, span: Span) -> Result<i64, SaltError> {
+    match range_bound(value, span)? {
+        0 => Err(SaltError::runtime("Range step must not be zero", span)),
+        step => Ok(step),
+    }
+}
+
+/// The integers of a range `start..end` or `start..=end`, counting by `step`
+/// in either direction.
+pub struct RangeIter {
+    next: Option<i64>,
+    end: i64,
+    step: i64,
+    inclusive: bool,
+}
+
+impl RangeIter {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> Self {
+        Self {
+            next: Some(start),
+            end,
+            step,
+            inclusive,
+        }
+    }
+}
+
+impl Iterator for RangeIter {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let i = self.next?;
+        let in_range = match (self.step > 0, self.inclusive) {
+            (true, false) => i < self.end,
+            (true, true) => i <= self.end,
+            (false, false) => i > self.end,
+            (false, true) => i >= self.end,
+        };
+        if !in_range {
+            self.next = None;
+            return None;
+        }
+        self.next = i.checked_add(self.step);
+        Some(i)
+    }
+}
+
+/// The items a `for` loop visits: the elements of an array or the keys of a
+/// map, in order. This is a snapshot, so the loop body may modify the
+/// collection.
+pub fn iteration_items(value: Value, span: Span) -> Result<Vec<Value>, SaltError> {
+    match value {
+        Value::Array(elements) => Ok(elements.borrow().clone()),
+        Value::Map(map) => Ok(map.borrow().keys().map(Key::to_value).collect()),
+        other => Err(SaltError::runtime(
+            format!("Type error: cannot iterate over {}", other.type_name()),
+            span,
+        )),
+    }
+}
+
+pub fn index(target: Value, position: &Value, spans: &IndexSpans) -> Result<Value, SaltError> {
+    match target {
+        Value::Array(elements) => {
+            let elements = elements.borrow();
+            let i = array_index(position, elements.len(), spans)?;
+            Ok(elements[i].clone())
+        }
+        Value::Map(map) => {
+            let key = Key::from_value(position, spans.index)?;
+            map.borrow().get(&key).cloned().ok_or_else(|| {
+                SaltError::runtime(format!("Key {key} not found in Map"), spans.whole)
+            })
+        }
+        other => Err(index_type_error(&other, spans)),
+    }
+}
+
+pub fn set_index(
+    target: Value,
+    position: &Value,
+    value: Value,
+    spans: &IndexSpans,
+) -> Result<(), SaltError> {
+    match target {
+        Value::Array(elements) => {
+            let mut elements = elements.borrow_mut();
+            let i = array_index(position, elements.len(), spans)?;
+            elements[i] = value;
+        }
+        Value::Map(map) => {
+            let key = Key::from_value(position, spans.index)?;
+            map.borrow_mut().insert(key, value);
+        }
+        other => return Err(index_type_error(&other, spans)),
+    }
+    Ok(())
+}
+
+/// Checks that `position` is an Integer within the bounds of an array of
+/// length `length`.
+fn array_index(position: &Value, length: usize, spans: &IndexSpans) -> Result<usize, SaltError> {
+    let i = match position {
+        Value::Integer(i) => *i,
+        // Too large to be in bounds of any array.
+        Value::BigInt(_) => -1,
+        other => {
+            return Err(SaltError::runtime(
+                format!(
+                    "Type error: Array index must be an Integer, found {}",
+                    other.type_name()
+                ),
+                spans.index,
+            ))
+        }
+    };
+    match usize::try_from(i) {
+        Ok(i) if i < length => Ok(i),
+        _ => Err(SaltError::runtime(
+            format!("Index {position} is out of bounds for an Array of length {length}"),
+            spans.whole,
+        )),
+    }
+}
+
+fn index_type_error(target: &Value, spans: &IndexSpans) -> SaltError {
+    SaltError::runtime(
+        format!("Type error: cannot index into {}", target.type_name()),
+        spans.target,
+    )
+}
+
+/// Checks that the target of a field access is a struct instance.
+pub fn expect_instance(
+    target: Value,
+    field: &str,
+    target_span: Span,
+) -> Result<Rc<Instance>, SaltError> {
+    match target {
+        Value::Struct(instance) => Ok(instance),
+        other => Err(SaltError::runtime(
+            format!(
+                "Type error: cannot access field `{field}` on {}",
+                other.type_name()
+            ),
+            target_span,
+        )),
+    }
+}
+
+/// The error for a field that a struct does not have, with a note pointing
+/// at the struct definition when it is known.
+pub fn unknown_field(
+    struct_name: &str,
+    field: &str,
+    span: Span,
+    definition: Option<Span>,
+) -> SaltError {
+    let error = SaltError::runtime(
+        format!("Struct `{struct_name}` has no field `{field}`"),
+        span,
+    );
+    match definition {
+        Some(definition) => error.with_note(
+            format!("struct `{struct_name}` defined here"),
+            Some(definition),
+        ),
+        None => error,
+    }
+}
+
+/// Checks that a function declared with `expected` parameters was given as
+/// many arguments.
+pub fn check_arity(
+    name: &str,
+    expected: usize,
+    given: usize,
+    span: Span,
+    definition: Span,
+) -> Result<(), SaltError> {
+    if expected != given {
+        let plural = if expected == 1 { "" } else { "s" };
+        let error = SaltError::runtime(
+            format!("Function `{name}` expects {expected} argument{plural} but was given {given}"),
+            span,
+        )
+        .with_note(
+            format!("function `{name}` declared with {expected} parameter{plural} here"),
+            Some(definition),
+        );
+        return Err(error);
+    }
+    Ok(())
+}
+
+pub fn not_callable(callee: &Value, span: Span) -> SaltError {
+    SaltError::runtime(
+        format!("Type error: cannot call {}", callee.type_name()),
+        span,
+    )
+}
+
+/// Whether `left == right` holds, with the numeric promotion of `==`. Literal
+/// patterns match the values they are equal to in this sense.
+pub fn equal(left: &Value, right: &Value) -> bool {
+    let (left, right) = Value::promote(left.clone(), right.clone());
+    left == right
+}
+
+/// The error for a `match` in `module` that no arm matches. Unqualified
+/// patterns only match enums of that module, so enums of other modules get
+/// a note saying how to match them.
+pub fn no_match(scrutinee: &Value, module: usize, span: Span) -> SaltError {
+    let error = SaltError::runtime(format!("No match arm matches {scrutinee}"), span);
+    match scrutinee {
+        Value::Enum(value) if value.module != module => {
+            let (name, variant) = (&value.enum_name, &value.variant);
+            error.with_note(
+                format!(
+                    "this `{name}` is an enum of another module; qualify its patterns \
+                     with the module, as in `module.{name}::{variant}`"
+                ),
+                None,
+            )
+        }
+        _ => error,
+    }
+}
+
+/// The name and module of `target`, if the resolver found it to name an
+/// import, given the `imports` of the module using it.
+pub fn imported_module<'e>(
+    target: &'e Expr,
+    imports: &HashMap<String, usize>,
+) -> Option<(&'e str, usize)> {
+    let Expr::Name(name) = target else {
+        return None;
+    };
+    if name.resolution != Resolution::Import {
+        return None;
+    }
+    let module = imports[&name.name];
+    Some((&name.name, module))
+}
+
+/// The error for a variable that the resolver found but that does not have
+/// a value yet, such as a global whose initializer has not run.
+pub fn no_such_variable(name: &Name) -> SaltError {
+    SaltError::runtime(format!("No such variable: {}", name.name), name.span)
+}
+
+/// The error for a struct literal naming a struct its module does not
+/// define.
+pub fn no_such_struct(literal: &StructLiteral) -> SaltError {
+    let name = match &literal.module {
+        Some(module) => format!("{}.{}", module.name, literal.name),
+        None => literal.name.clone(),
+    };
+    SaltError::runtime(format!("No such struct: {name}"), literal.span)
+}
+
+/// Like `no_such_variable`, for the callee of the call at `span`.
+pub fn no_such_function(name: &Name, span: Span) -> SaltError {
+    SaltError::runtime(format!("No such function: {}", name.name), span)
+}
+
+pub fn unknown_member(module_name: &str, member: &Name) -> SaltError {
+    SaltError::runtime(
+        format!("Module `{module_name}` has no member `{}`", member.name),
+        member.span,
+    )
+}
+
+pub fn time() -> Value {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    Value::Integer(millis as i64)
+}
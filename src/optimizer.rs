@@ -0,0 +1,327 @@
+use std::mem;
+use std::rc::Rc;
+
+use crate::ast::{
+    Assignment, BinaryOp, BinaryOpType, Block, Else, Expr, Global, Iterable, Literal, Program,
+    Statement, Target, UnaryOpType,
+};
+use crate::ops;
+use crate::span::Span;
+use crate::value::Value;
+
+/// Simplifies a resolved program without changing what it computes or the
+/// errors it reports:
+///
+/// - operators whose operands are literals are folded into a literal, unless
+///   applying them is an error, such as a division by zero;
+/// - `if` and `while` statements whose condition is a Boolean literal are
+///   replaced by the branch that runs, if any;
+/// - statements after an unconditional `return` are dropped;
+/// - identities such as `x * 1` and `x - 0` are reduced to `x` when `x` is
+///   known to be a number, since otherwise they may be type errors.
+///
+/// It runs after the resolver, so the statements of a block can be moved
+/// into the enclosing block without changing which variables they refer to.
+pub fn optimize(program: &mut Program) {
+    for global in &mut program.globals {
+        match global {
+            Global::Function(function) => optimize_block(&mut function.block),
+            Global::Variable(variable) => optimize_expr(&mut variable.expr),
+            Global::Struct(_) | Global::Enum(_) | Global::Import(_) => (),
+        }
+    }
+}
+
+fn optimize_block(block: &mut Block) {
+    for statement in mem::take(&mut block.statements) {
+        optimize_statement(statement, &mut block.statements);
+        if let Some(Statement::Return(_)) = block.statements.last() {
+            break;
+        }
+    }
+}
+
+/// Appends the optimized form of `statement`, which may be any number of
+/// statements, to `statements`.
+fn optimize_statement(statement: Statement, statements: &mut Vec<Statement>) {
+    let statement = match statement {
+        Statement::Let(mut let_stmt) => {
+            optimize_expr(&mut let_stmt.expr);
+            Statement::Let(let_stmt)
+        }
+        Statement::If(if_stmt) => {
+            match optimize_else(Else::If(Box::new(if_stmt))) {
+                Some(Else::Block(block)) => statements.extend(block.statements),
+                Some(Else::If(if_stmt)) => statements.push(Statement::If(*if_stmt)),
+                None => (),
+            }
+            return;
+        }
+        Statement::While(mut while_loop) => {
+            optimize_expr(&mut while_loop.condition);
+            if literal_bool(&while_loop.condition) == Some(false) {
+                return;
+            }
+            optimize_block(&mut while_loop.body);
+            Statement::While(while_loop)
+        }
+        Statement::For(mut for_loop) => {
+            match &mut for_loop.iterable {
+                Iterable::Range(range) => {
+                    optimize_expr(&mut range.start);
+                    optimize_expr(&mut range.end);
+                    if let Some(step) = &mut range.step {
+                        optimize_expr(step);
+                    }
+                }
+                Iterable::Expr(expr) => optimize_expr(expr),
+            }
+            optimize_block(&mut for_loop.body);
+            Statement::For(for_loop)
+        }
+        Statement::Return(mut return_stmt) => {
+            optimize_expr(&mut return_stmt.expr);
+            Statement::Return(return_stmt)
+        }
+        Statement::Expr(mut expr) => {
+            optimize_expr(&mut expr);
+            Statement::Expr(expr)
+        }
+        Statement::Assignment(mut assignment) => {
+            optimize_assignment(&mut assignment);
+            Statement::Assignment(assignment)
+        }
+        Statement::Print(mut print) => {
+            optimize_expr(&mut print.expr);
+            Statement::Print(print)
+        }
+        Statement::Match(mut match_stmt) => {
+            optimize_expr(&mut match_stmt.scrutinee);
+            for arm in &mut match_stmt.arms {
+                optimize_block(&mut arm.body);
+            }
+            Statement::Match(match_stmt)
+        }
+        statement @ (Statement::Break(_) | Statement::Continue(_)) => statement,
+    };
+    statements.push(statement);
+}
+
+/// Optimizes an `else` branch, or a whole `if` statement as `Else::If`.
+/// Returns `None` if no branch can run.
+fn optimize_else(else_branch: Else) -> Option<Else> {
+    let mut if_stmt = match else_branch {
+        Else::Block(mut block) => {
+            optimize_block(&mut block);
+            return Some(Else::Block(block));
+        }
+        Else::If(if_stmt) => if_stmt,
+    };
+    optimize_expr(&mut if_stmt.condition);
+    match literal_bool(&if_stmt.condition) {
+        Some(true) => optimize_else(Else::Block(if_stmt.body)),
+        Some(false) => if_stmt.else_branch.and_then(optimize_else),
+        None => {
+            optimize_block(&mut if_stmt.body);
+            if_stmt.else_branch = if_stmt.else_branch.and_then(optimize_else);
+            Some(Else::If(if_stmt))
+        }
+    }
+}
+
+fn optimize_assignment(assignment: &mut Assignment) {
+    match &mut assignment.target {
+        Target::Name(_) => (),
+        Target::Index(index) => {
+            optimize_expr(&mut index.target);
+            optimize_expr(&mut index.index);
+        }
+        Target::Field(field) => optimize_expr(&mut field.target),
+    }
+    optimize_expr(&mut assignment.expr);
+}
+
+fn optimize_expr(expr: &mut Expr) {
+    let simplified = match expr {
+        Expr::Literal(_) | Expr::Name(_) | Expr::Time(_) => None,
+        Expr::Call(call) => {
+            optimize_expr(&mut call.callee);
+            optimize_exprs(&mut call.arguments);
+            None
+        }
+        Expr::Lambda(lambda) => {
+            let function = Rc::get_mut(&mut lambda.function)
+                .expect("lambdas are not shared before the program runs");
+            optimize_block(&mut function.block);
+            None
+        }
+        Expr::Array(array) => {
+            optimize_exprs(&mut array.elements);
+            None
+        }
+        Expr::Map(map) => {
+            for (key, value) in &mut map.entries {
+                optimize_expr(key);
+                optimize_expr(value);
+            }
+            None
+        }
+        Expr::StructLiteral(literal) => {
+            for (_, value) in &mut literal.fields {
+                optimize_expr(value);
+            }
+            None
+        }
+        Expr::EnumLiteral(literal) => {
+            optimize_exprs(&mut literal.arguments);
+            None
+        }
+        Expr::Index(index) => {
+            optimize_expr(&mut index.target);
+            optimize_expr(&mut index.index);
+            None
+        }
+        Expr::Field(field) => {
+            optimize_expr(&mut field.target);
+            None
+        }
+        Expr::UnaryOp(unary_op) => {
+            optimize_expr(&mut unary_op.expr);
+            match unary_op.expr.as_ref() {
+                Expr::Literal(operand) => {
+                    ops::unary(unary_op.op_type, operand.value.clone(), unary_op.span)
+                        .ok()
+                        .map(|value| literal(value, unary_op.span))
+                }
+                _ => None,
+            }
+        }
+        Expr::BinaryOp(binary_op) => {
+            optimize_expr(&mut binary_op.left);
+            optimize_expr(&mut binary_op.right);
+            fold_binary(binary_op).or_else(|| simplify_identity(binary_op))
+        }
+        Expr::Match(match_expr) => {
+            optimize_expr(&mut match_expr.scrutinee);
+            for arm in &mut match_expr.arms {
+                optimize_expr(&mut arm.body);
+            }
+            None
+        }
+    };
+    if let Some(simplified) = simplified {
+        *expr = simplified;
+    }
+}
+
+fn optimize_exprs(exprs: &mut [Expr]) {
+    for expr in exprs {
+        optimize_expr(expr);
+    }
+}
+
+/// Folds an operator whose operands are literals, or `&&` and `||` whose
+/// left operand decides the result.
+fn fold_binary(op: &BinaryOp) -> Option<Expr> {
+    let Expr::Literal(left) = op.left.as_ref() else {
+        return None;
+    };
+    if let BinaryOpType::And | BinaryOpType::Or = op.op_type {
+        let short_circuit = op.op_type == BinaryOpType::Or;
+        let value = match (&left.value, literal_bool(&op.right)) {
+            (Value::Boolean(left), _) if *left == short_circuit => *left,
+            (Value::Boolean(_), Some(right)) => right,
+            _ => return None,
+        };
+        return Some(literal(Value::Boolean(value), op.span));
+    }
+    let Expr::Literal(right) = op.right.as_ref() else {
+        return None;
+    };
+    ops::binary(op.op_type, left.value.clone(), right.value.clone(), op.span)
+        .ok()
+        .map(|value| literal(value, op.span))
+}
+
+/// The type of number an expression is known to evaluate to, if it
+/// evaluates at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Number {
+    Integer,
+    /// An Integer or a Float.
+    Any,
+}
+
+fn known_number(expr: &Expr) -> Option<Number> {
+    match expr {
+        Expr::Literal(literal) => match literal.value {
+            Value::Integer(_) | Value::BigInt(_) => Some(Number::Integer),
+            Value::Float(_) => Some(Number::Any),
+            _ => None,
+        },
+        Expr::UnaryOp(unary_op) if unary_op.op_type == UnaryOpType::Negate => {
+            Some(known_number(&unary_op.expr).unwrap_or(Number::Any))
+        }
+        Expr::BinaryOp(binary_op) => {
+            let operands = (
+                known_number(&binary_op.left),
+                known_number(&binary_op.right),
+            );
+            let integers = operands == (Some(Number::Integer), Some(Number::Integer));
+            match binary_op.op_type {
+                // `+` also concatenates strings.
+                BinaryOpType::Addition if operands.0.is_none() || operands.1.is_none() => None,
+                BinaryOpType::Addition
+                | BinaryOpType::Subtraction
+                | BinaryOpType::Multiplication
+                | BinaryOpType::Division
+                | BinaryOpType::Modulo => Some(if integers {
+                    Number::Integer
+                } else {
+                    Number::Any
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Reduces `x * 1`, `1 * x`, `x / 1` and `x - 0` to `x` when `x` is a number,
+/// and `x + 0` and `0 + x` when it is an Integer, as adding an Integer zero
+/// to the Float `-0.0` gives `0.0`.
+fn simplify_identity(op: &mut BinaryOp) -> Option<Expr> {
+    let is_integer = |expr: &Expr, n: i64| matches!(expr, Expr::Literal(Literal { value: Value::Integer(i), .. }) if *i == n);
+    let (left, right) = (known_number(&op.left), known_number(&op.right));
+    let operand = match op.op_type {
+        BinaryOpType::Multiplication if is_integer(&op.right, 1) && left.is_some() => &mut op.left,
+        BinaryOpType::Multiplication if is_integer(&op.left, 1) && right.is_some() => &mut op.right,
+        BinaryOpType::Division if is_integer(&op.right, 1) && left.is_some() => &mut op.left,
+        BinaryOpType::Subtraction if is_integer(&op.right, 0) && left.is_some() => &mut op.left,
+        BinaryOpType::Addition if is_integer(&op.right, 0) && left == Some(Number::Integer) => {
+            &mut op.left
+        }
+        BinaryOpType::Addition if is_integer(&op.left, 0) && right == Some(Number::Integer) => {
+            &mut op.right
+        }
+        _ => return None,
+    };
+    Some(mem::replace(
+        operand.as_mut(),
+        literal(Value::Unit, op.span),
+    ))
+}
+
+fn literal(value: Value, span: Span) -> Expr {
+    Expr::Literal(Literal { value, span })
+}
+
+fn literal_bool(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(Literal {
+            value: Value::Boolean(b),
+            ..
+        }) => Some(*b),
+        _ => None,
+    }
+}
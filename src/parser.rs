@@ -1,151 +1,720 @@
+use std::path::Path;
+use std::rc::Rc;
 use std::{iter::Peekable, slice::Iter};
 
 use crate::ast::{
-    BinaryOp, BinaryOpType, Assignment, Block, Call, Expr, Function, Global, IfStmt, Print, Program,
-    Return, Statement, Time, UnaryOp, UnaryOpType, WhileLoop,
+    Arm, Array, Assignment, BinaryOp, BinaryOpType, Block, Break, Call, Continue, Else, Enum,
+    EnumLiteral, Expr, Field, ForLoop, Function, Global, GlobalVariable, IfStmt, Import, Index,
+    Iterable, Lambda, Let, Literal, MapLiteral, Match, Name, Pattern, Print, Program, Range,
+    Return, Statement, Struct, StructLiteral, Target, Time, UnaryOp, UnaryOpType, Variant,
+    VariantPattern, WhileLoop,
 };
-use crate::token::Token;
+use crate::bigint::BigInt;
+use crate::error::SaltError;
+use crate::span::Span;
+use crate::token::{SpannedToken, Token};
 use crate::value::Value;
 
 pub struct Parser<'a> {
-    tokens: Peekable<Iter<'a, Token>>,
+    tokens: Peekable<Iter<'a, SpannedToken>>,
+    previous: Option<&'a SpannedToken>,
+    errors: Vec<SaltError>,
+    /// Number of loops enclosing the statement being parsed.
+    loop_depth: usize,
+    /// Cleared while parsing the header of an `if`, `while` or `for`, where
+    /// `name {` opens the body rather than a struct literal.
+    struct_literals: bool,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: Iter<'a, Token>) -> Self {
+    pub fn new(tokens: Iter<'a, SpannedToken>) -> Self {
         Self {
             tokens: tokens.peekable(),
+            previous: None,
+            errors: Vec::new(),
+            loop_depth: 0,
+            struct_literals: true,
         }
     }
 
-    pub fn parse(&mut self) -> Program {
+    /// Parses the whole token stream, recovering from syntax errors so that
+    /// all of them are reported. The returned program omits whatever could
+    /// not be parsed.
+    pub fn parse(&mut self) -> (Program, Vec<SaltError>) {
         let mut globals = Vec::new();
         while !self.has_ended() {
-            let function = self.parse_function();
-            globals.push(function);
+            match self.parse_global() {
+                Ok(global) => globals.push(global),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_global();
+                }
+            }
+        }
+        let errors = std::mem::take(&mut self.errors);
+        (Program { globals }, errors)
+    }
+
+    /// Skips tokens until the start of the next function, struct or enum.
+    fn synchronize_global(&mut self) {
+        while !matches!(
+            self.peek(),
+            Token::Function
+                | Token::Struct
+                | Token::Enum
+                | Token::Import
+                | Token::Const
+                | Token::Global
+                | Token::End
+        ) {
+            self.tokens.next();
+        }
+    }
+
+    /// Skips tokens until the end of the current statement: just past a `;`,
+    /// or before a `}`, the start of a global or a keyword that starts a statement.
+    fn synchronize_statement(&mut self) {
+        if matches!(self.previous, Some(spanned) if spanned.token == Token::Semicolon) {
+            return;
+        }
+        loop {
+            match self.peek() {
+                Token::RCurly
+                | Token::Function
+                | Token::Struct
+                | Token::Enum
+                | Token::Import
+                | Token::Const
+                | Token::Global
+                | Token::End
+                | Token::Let
+                | Token::If
+                | Token::While
+                | Token::For
+                | Token::Return
+                | Token::Break
+                | Token::Continue
+                | Token::Match
+                | Token::Print => return,
+                Token::Semicolon => {
+                    self.tokens.next();
+                    return;
+                }
+                _ => {
+                    self.tokens.next();
+                }
+            }
+        }
+    }
+
+    fn parse_global(&mut self) -> Result<Global, SaltError> {
+        match self.peek() {
+            Token::Struct => self.parse_struct(),
+            Token::Enum => self.parse_enum(),
+            Token::Import => self.parse_import(),
+            Token::Const | Token::Global => self.parse_global_variable(),
+            _ => self.parse_function(),
+        }
+    }
+
+    fn parse_import(&mut self) -> Result<Global, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Import)?;
+        let span = self.peek_span();
+        let path = match self.advance()? {
+            Token::String(path) => path.clone(),
+            Token::Name(name) => format!("{name}.salt"),
+            other => {
+                return Err(SaltError::parse(
+                    format!("Expected a module name or path but found {other}"),
+                    span,
+                ))
+            }
+        };
+        self.advance_specific(&Token::Semicolon)?;
+        let name = Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let span = self.span_from(start);
+        Ok(Global::Import(Import { path, name, span }))
+    }
+
+    fn parse_global_variable(&mut self) -> Result<Global, SaltError> {
+        let start = self.peek_span();
+        let constant = self.advance()? == &Token::Const;
+        let name = self.parse_name()?;
+        self.advance_specific(&Token::Equal)?;
+        let expr = self.parse_expression()?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        Ok(Global::Variable(GlobalVariable {
+            name,
+            expr,
+            constant,
+            span,
+            slots: 0,
+        }))
+    }
+
+    fn parse_struct(&mut self) -> Result<Global, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Struct)?;
+        let name = self.parse_name()?;
+        self.advance_specific(&Token::LCurly)?;
+        let mut fields: Vec<String> = Vec::new();
+        loop {
+            if self.peek() == &Token::RCurly {
+                self.advance()?;
+                break;
+            }
+            let span = self.peek_span();
+            let field = self.parse_name()?;
+            if fields.contains(&field) {
+                return Err(SaltError::parse(
+                    format!("Field `{field}` is declared more than once in struct `{name}`"),
+                    span,
+                ));
+            }
+            fields.push(field);
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RCurly => break,
+                Token::Comma => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `}}` in struct definition but found {other}"),
+                        span,
+                    ))
+                }
+            }
         }
-        Program { globals }
+        let span = self.span_from(start);
+        Ok(Global::Struct(Struct { name, fields, span }))
     }
 
-    fn parse_function(&mut self) -> Global {
-        self.advance();
-        let name = self.parse_name();
-        let parameters = self.parse_parameters();
-        let block = self.parse_block();
+    fn parse_enum(&mut self) -> Result<Global, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Enum)?;
+        let name = self.parse_name()?;
+        self.advance_specific(&Token::LCurly)?;
+        let mut variants: Vec<Variant> = Vec::new();
+        loop {
+            if self.peek() == &Token::RCurly {
+                self.advance()?;
+                break;
+            }
+            let variant_start = self.peek_span();
+            let variant = self.parse_name()?;
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(SaltError::parse(
+                    format!("Variant `{variant}` is declared more than once in enum `{name}`"),
+                    variant_start,
+                ));
+            }
+            let fields = match self.peek() {
+                Token::LParen => self.parse_parameters()?,
+                _ => Vec::new(),
+            };
+            variants.push(Variant {
+                name: variant,
+                fields,
+                span: self.span_from(variant_start),
+            });
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RCurly => break,
+                Token::Comma => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `}}` in enum definition but found {other}"),
+                        span,
+                    ))
+                }
+            }
+        }
+        let span = self.span_from(start);
+        Ok(Global::Enum(Enum {
+            name,
+            variants,
+            span,
+        }))
+    }
+
+    fn parse_function(&mut self) -> Result<Global, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Function)?;
+        let name = self.parse_name()?;
+        let parameters = self.parse_parameters()?;
+        let span = self.span_from(start);
+        let block = self.parse_block()?;
         let function = Function {
             name,
             parameters,
             block,
+            span,
+            slots: 0,
+            captures: Vec::new(),
         };
-        Global::Function(function)
+        Ok(Global::Function(function))
     }
 
-    fn parse_parameters(&mut self) -> Vec<String> {
-        self.advance_specific(&Token::LParen);
+    fn parse_parameters(&mut self) -> Result<Vec<String>, SaltError> {
+        self.advance_specific(&Token::LParen)?;
         let mut parameters = Vec::new();
         if self.peek() == &Token::RParen {
-            self.advance();
-            return parameters;
+            self.advance()?;
+            return Ok(parameters);
         }
-        let first_parameter = self.parse_name();
+        let first_parameter = self.parse_name()?;
         parameters.push(first_parameter);
         loop {
-            match self.advance() {
-                Token::RParen => return parameters,
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RParen => return Ok(parameters),
                 Token::Comma => (),
-                other => panic!("Error while trying to parse parameter: {other:?}"),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `)` in parameter list but found {other}"),
+                        span,
+                    ))
+                }
             }
-            let parameter = self.parse_name();
+            let parameter = self.parse_name()?;
             parameters.push(parameter);
         }
     }
 
-    fn parse_block(&mut self) -> Block {
-        self.advance_specific(&Token::LCurly);
+    fn parse_block(&mut self) -> Result<Block, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::LCurly)?;
         let mut statements = Vec::new();
-        while self.peek() != &Token::RCurly {
-            let statement = self.parse_statement();
-            statements.push(statement);
+        while !matches!(
+            self.peek(),
+            Token::RCurly
+                | Token::Function
+                | Token::Struct
+                | Token::Enum
+                | Token::Import
+                | Token::Const
+                | Token::Global
+                | Token::End
+        ) {
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_statement();
+                }
+            }
         }
-        self.advance_specific(&Token::RCurly);
-        Block { statements }
+        self.advance_specific(&Token::RCurly)?;
+        let span = self.span_from(start);
+        Ok(Block { statements, span })
     }
 
-    fn parse_statement(&mut self) -> Statement {
+    fn parse_statement(&mut self) -> Result<Statement, SaltError> {
         match self.peek() {
+            Token::Let => self.parse_let(),
             Token::If => self.parse_if(),
             Token::While => self.parse_while(),
+            Token::For => self.parse_for(),
             Token::Return => self.parse_return(),
             Token::Print => self.parse_print(),
+            Token::Break => self.parse_break(),
+            Token::Continue => self.parse_continue(),
+            Token::Match => {
+                let match_stmt = self.parse_match(Self::parse_block)?;
+                Ok(Statement::Match(match_stmt))
+            }
             _ => {
-                let expr = self.parse_expression();
-                match self.advance() {
-                    Token::Semicolon => {
-                        Statement::Expr(expr)
-                    }
+                let start = self.peek_span();
+                let expr = self.parse_expression()?;
+                let span = self.peek_span();
+                match self.advance()? {
+                    Token::Semicolon => Ok(Statement::Expr(expr)),
                     Token::Equal => {
-                        let name = match expr {
-                            Expr::Name(name) => name,
-                            _ => panic!("Cannot assign to {expr:?}")
+                        let target = match expr {
+                            Expr::Name(name) => Target::Name(name),
+                            Expr::Index(index) => Target::Index(index),
+                            Expr::Field(field) => Target::Field(field),
+                            _ => {
+                                return Err(SaltError::parse(
+                                    "Invalid assignment target",
+                                    expr.span(),
+                                ))
+                            }
+                        };
+                        let rhs = self.parse_expression()?;
+                        self.advance_specific(&Token::Semicolon)?;
+                        let span = self.span_from(start);
+                        let assignment = Assignment {
+                            target,
+                            expr: rhs,
+                            span,
                         };
-                        let rhs = self.parse_expression();
-                        self.advance_specific(&Token::Semicolon);
-                        let assignment = Assignment { name, expr: rhs };
-                        Statement::Assignment(assignment)
+                        Ok(Statement::Assignment(assignment))
                     }
-                    _ => panic!("Error while trying to parse statement"),
+                    other => Err(SaltError::parse(
+                        format!("Expected `;` or `=` after expression but found {other}"),
+                        span,
+                    )),
                 }
-            },
+            }
         }
     }
 
-    fn parse_if(&mut self) -> Statement {
-        self.advance_specific(&Token::If);
-        let condition = self.parse_expression();
-        let body = self.parse_block();
-        let if_stmt = IfStmt { condition, body };
-        Statement::If(if_stmt)
+    fn parse_let(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Let)?;
+        let name = self.parse_name()?;
+        self.advance_specific(&Token::Equal)?;
+        let expr = self.parse_expression()?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        let let_stmt = Let {
+            name,
+            expr,
+            span,
+            slot: 0,
+        };
+        Ok(Statement::Let(let_stmt))
+    }
+
+    fn parse_if(&mut self) -> Result<Statement, SaltError> {
+        let if_stmt = self.parse_if_stmt()?;
+        Ok(Statement::If(if_stmt))
+    }
+
+    fn parse_if_stmt(&mut self) -> Result<IfStmt, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::If)?;
+        let condition = self.parse_header_expression()?;
+        let body = self.parse_block()?;
+        let else_branch = match self.peek() {
+            Token::Else => {
+                self.advance()?;
+                match self.peek() {
+                    Token::If => Some(Else::If(Box::new(self.parse_if_stmt()?))),
+                    _ => Some(Else::Block(self.parse_block()?)),
+                }
+            }
+            _ => None,
+        };
+        let span = self.span_from(start);
+        Ok(IfStmt {
+            condition,
+            body,
+            else_branch,
+            span,
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::While)?;
+        let condition = self.parse_header_expression()?;
+        let body = self.parse_loop_body()?;
+        let span = self.span_from(start);
+        let while_loop = WhileLoop {
+            condition,
+            body,
+            span,
+        };
+        Ok(Statement::While(while_loop))
+    }
+
+    fn parse_for(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::For)?;
+        let variable = self.parse_name()?;
+        self.advance_specific(&Token::In)?;
+        let iterable = self.parse_iterable()?;
+        let body = self.parse_loop_body()?;
+        let span = self.span_from(start);
+        let for_loop = ForLoop {
+            variable,
+            iterable,
+            body,
+            span,
+            slot: 0,
+        };
+        Ok(Statement::For(for_loop))
+    }
+
+    /// Parses either a range or a single expression to iterate over.
+    fn parse_iterable(&mut self) -> Result<Iterable, SaltError> {
+        let start = self.parse_header_expression()?;
+        let inclusive = match self.peek() {
+            Token::DotDot => false,
+            Token::DotDotEqual => true,
+            _ => return Ok(Iterable::Expr(start)),
+        };
+        self.advance()?;
+        let end = self.parse_header_expression()?;
+        // `step` is only a keyword in this position.
+        let step = match self.peek() {
+            Token::Name(name) if name == "step" => {
+                self.advance()?;
+                Some(self.parse_header_expression()?)
+            }
+            _ => None,
+        };
+        Ok(Iterable::Range(Box::new(Range {
+            start,
+            end,
+            step,
+            inclusive,
+        })))
     }
 
-    fn parse_while(&mut self) -> Statement {
-        self.advance_specific(&Token::While);
-        let condition = self.parse_expression();
+    fn parse_loop_body(&mut self) -> Result<Block, SaltError> {
+        self.loop_depth += 1;
         let body = self.parse_block();
-        let while_loop = WhileLoop { condition, body };
-        Statement::While(while_loop)
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_break(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Break)?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        self.check_in_loop("break", span)?;
+        Ok(Statement::Break(Break { span }))
+    }
+
+    fn parse_continue(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Continue)?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        self.check_in_loop("continue", span)?;
+        Ok(Statement::Continue(Continue { span }))
+    }
+
+    fn check_in_loop(&self, keyword: &str, span: Span) -> Result<(), SaltError> {
+        if self.loop_depth == 0 {
+            return Err(SaltError::parse(
+                format!("`{keyword}` outside of a loop"),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_return(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Return)?;
+        let expr = self.parse_expression()?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        let return_stmt = Return { expr, span };
+        Ok(Statement::Return(return_stmt))
+    }
+
+    fn parse_print(&mut self) -> Result<Statement, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Print)?;
+        self.advance_specific(&Token::LParen)?;
+        let expr = self.parse_expression()?;
+        self.advance_specific(&Token::RParen)?;
+        self.advance_specific(&Token::Semicolon)?;
+        let span = self.span_from(start);
+        let print = Print { expr, span };
+        Ok(Statement::Print(print))
+    }
+
+    /// Parses a `match`, using `parse_body` for the body of each arm. Commas
+    /// between arms are optional after a block.
+    fn parse_match<T>(
+        &mut self,
+        parse_body: fn(&mut Self) -> Result<T, SaltError>,
+    ) -> Result<Match<T>, SaltError> {
+        let start = self.peek_span();
+        self.advance_specific(&Token::Match)?;
+        let scrutinee = self.parse_header_expression()?;
+        self.advance_specific(&Token::LCurly)?;
+        let mut arms = Vec::new();
+        while self.peek() != &Token::RCurly {
+            let pattern = self.parse_pattern()?;
+            self.advance_specific(&Token::FatArrow)?;
+            let ends_with_block = self.peek() == &Token::LCurly;
+            let body = parse_body(self)?;
+            let span = self.span_from(pattern.span());
+            arms.push(Arm {
+                pattern,
+                body,
+                span,
+            });
+            match self.peek() {
+                Token::Comma => {
+                    self.advance()?;
+                }
+                Token::RCurly => (),
+                _ if ends_with_block => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `}}` after match arm but found {other}"),
+                        self.peek_span(),
+                    ))
+                }
+            }
+        }
+        self.advance_specific(&Token::RCurly)?;
+        let span = self.span_from(start);
+        Ok(Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            span,
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, SaltError> {
+        let start = self.peek_span();
+        let literal = |value| Ok(Pattern::Literal(Literal { value, span: start }));
+        match self.advance()? {
+            Token::Name(name) if name == "_" => Ok(Pattern::Wildcard(start)),
+            Token::Name(module) if self.peek() == &Token::Dot => {
+                let module = Name::new(module.clone(), start);
+                self.advance()?;
+                let name = self.parse_name()?;
+                self.parse_variant_pattern(Some(module), name, start)
+            }
+            Token::Name(name) if self.peek() == &Token::ColonColon => {
+                self.parse_variant_pattern(None, name.clone(), start)
+            }
+            Token::Name(name) => Ok(Pattern::Binding(Name::new(name.clone(), start))),
+            Token::Integer(integer) => literal(Value::Integer(*integer)),
+            Token::BigInteger(integer) => literal(Value::BigInt(Rc::new(integer.clone()))),
+            Token::Float(float) => literal(Value::Float(*float)),
+            Token::String(string) => literal(Value::String(string.as_str().into())),
+            Token::True => literal(Value::Boolean(true)),
+            Token::False => literal(Value::Boolean(false)),
+            Token::Minus => {
+                let span = self.peek_span();
+                let value = match self.advance()? {
+                    Token::Integer(integer) => Value::from_bigint(BigInt::from_i64(*integer).neg()),
+                    Token::BigInteger(integer) => Value::from_bigint(integer.neg()),
+                    Token::Float(float) => Value::Float(-float),
+                    other => {
+                        return Err(SaltError::parse(
+                            format!("Expected a number after `-` in pattern but found {other}"),
+                            span,
+                        ))
+                    }
+                };
+                Ok(Pattern::Literal(Literal {
+                    value,
+                    span: self.span_from(start),
+                }))
+            }
+            other => Err(SaltError::parse(
+                format!("Expected a pattern but found {other}"),
+                start,
+            )),
+        }
     }
 
-    fn parse_return(&mut self) -> Statement {
-        self.advance_specific(&Token::Return);
+    /// Parses `Enum::Variant(fields...)` after the enum name, which started
+    /// at `start` with its module, if any.
+    fn parse_variant_pattern(
+        &mut self,
+        module: Option<Name>,
+        enum_name: String,
+        start: Span,
+    ) -> Result<Pattern, SaltError> {
+        self.advance_specific(&Token::ColonColon)?;
+        let variant_span = self.peek_span();
+        let variant = Name::new(self.parse_name()?, variant_span);
+        let mut fields = Vec::new();
+        if self.peek() == &Token::LParen {
+            self.advance()?;
+            while self.peek() != &Token::RParen {
+                fields.push(self.parse_pattern()?);
+                if self.peek() != &Token::RParen {
+                    self.advance_specific(&Token::Comma)?;
+                }
+            }
+            self.advance()?;
+        }
+        let span = self.span_from(start);
+        Ok(Pattern::Variant(VariantPattern {
+            module,
+            enum_name,
+            variant,
+            fields,
+            span,
+        }))
+    }
+    fn parse_name(&mut self) -> Result<String, SaltError> {
+        let span = self.peek_span();
+        match self.advance()? {
+            Token::Name(name) => Ok(name.clone()),
+            other => Err(SaltError::parse(
+                format!("Expected a name but found {other}"),
+                span,
+            )),
+        }
+    }
+
+    /// Parses an expression directly followed by a block.
+    fn parse_header_expression(&mut self) -> Result<Expr, SaltError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
         let expr = self.parse_expression();
-        self.advance_specific(&Token::Semicolon);
-        let return_stmt = Return { expr };
-        Statement::Return(return_stmt)
+        self.struct_literals = struct_literals;
+        expr
     }
 
-    fn parse_print(&mut self) -> Statement {
-        self.advance_specific(&Token::Print);
-        self.advance_specific(&Token::LParen);
+    /// Parses an expression enclosed in delimiters, where struct literals
+    /// are unambiguous again.
+    fn parse_nested_expression(&mut self) -> Result<Expr, SaltError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
         let expr = self.parse_expression();
-        self.advance_specific(&Token::RParen);
-        self.advance_specific(&Token::Semicolon);
-        let print = Print { expr };
-        Statement::Print(print)
+        self.struct_literals = struct_literals;
+        expr
     }
 
-    fn parse_name(&mut self) -> String {
-        match self.advance() {
-            Token::Name(name) => name.clone(),
-            other => panic!("Error while trying to parse name: {other:?}"),
+    fn parse_expression(&mut self) -> Result<Expr, SaltError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, SaltError> {
+        let mut current = self.parse_and()?;
+        while self.peek() == &Token::DoublePipe {
+            self.advance()?;
+            let right = self.parse_and()?;
+            let span = current.span().to(right.span());
+            let expr = BinaryOp {
+                op_type: BinaryOpType::Or,
+                left: Box::new(current),
+                right: Box::new(right),
+                span,
+            };
+            current = Expr::BinaryOp(expr);
         }
+        Ok(current)
     }
 
-    fn parse_expression(&mut self) -> Expr {
-        self.parse_comparison()
+    fn parse_and(&mut self) -> Result<Expr, SaltError> {
+        let mut current = self.parse_comparison()?;
+        while self.peek() == &Token::DoubleAmpersand {
+            self.advance()?;
+            let right = self.parse_comparison()?;
+            let span = current.span().to(right.span());
+            let expr = BinaryOp {
+                op_type: BinaryOpType::And,
+                left: Box::new(current),
+                right: Box::new(right),
+                span,
+            };
+            current = Expr::BinaryOp(expr);
+        }
+        Ok(current)
     }
 
-    fn parse_comparison(&mut self) -> Expr {
-        let left = self.parse_addition_and_subtraction();
+    fn parse_comparison(&mut self) -> Result<Expr, SaltError> {
+        let left = self.parse_addition_and_subtraction()?;
         let comparison_type = match self.peek() {
             Token::DoubleEqual => BinaryOpType::EqualTo,
             Token::NotEqual => BinaryOpType::NotEqualTo,
@@ -153,144 +722,438 @@ impl<'a> Parser<'a> {
             Token::LessEqual => BinaryOpType::LessThanOrEqualTo,
             Token::Greater => BinaryOpType::GreaterThan,
             Token::GreaterEqual => BinaryOpType::GreaterThanOrEqualTo,
-            _ => return left,
+            _ => return Ok(left),
         };
-        self.advance();
-        let right = self.parse_addition_and_subtraction();
+        self.advance()?;
+        let right = self.parse_addition_and_subtraction()?;
+        let span = left.span().to(right.span());
         let op = BinaryOp {
             op_type: comparison_type,
             left: Box::new(left),
             right: Box::new(right),
+            span,
         };
-        Expr::BinaryOp(op)
+        Ok(Expr::BinaryOp(op))
     }
 
-    fn parse_addition_and_subtraction(&mut self) -> Expr {
-        let mut current = self.parse_multiplication_and_division();
+    fn parse_addition_and_subtraction(&mut self) -> Result<Expr, SaltError> {
+        let mut current = self.parse_multiplication_and_division()?;
         loop {
             let expr_type = match self.peek() {
                 Token::Plus => BinaryOpType::Addition,
                 Token::Minus => BinaryOpType::Subtraction,
-                _ => return current,
+                _ => return Ok(current),
             };
-            self.advance();
-            let right = self.parse_multiplication_and_division();
+            self.advance()?;
+            let right = self.parse_multiplication_and_division()?;
+            let span = current.span().to(right.span());
             let expr = BinaryOp {
                 op_type: expr_type,
                 left: Box::new(current),
                 right: Box::new(right),
+                span,
             };
             current = Expr::BinaryOp(expr);
         }
     }
 
-    fn parse_multiplication_and_division(&mut self) -> Expr {
-        let mut current = self.parse_unary();
+    fn parse_multiplication_and_division(&mut self) -> Result<Expr, SaltError> {
+        let mut current = self.parse_unary()?;
         loop {
             let expr_type = match self.peek() {
                 Token::Star => BinaryOpType::Multiplication,
                 Token::Slash => BinaryOpType::Division,
                 Token::Percent => BinaryOpType::Modulo,
-                _ => return current,
+                _ => return Ok(current),
             };
-            self.advance();
-            let right = self.parse_unary();
+            self.advance()?;
+            let right = self.parse_unary()?;
+            let span = current.span().to(right.span());
             let expr = BinaryOp {
                 op_type: expr_type,
                 left: Box::new(current),
                 right: Box::new(right),
+                span,
             };
             current = Expr::BinaryOp(expr);
         }
     }
 
-    fn parse_unary(&mut self) -> Expr {
-        match self.advance() {
-            Token::LParen => {
-                let expression = self.parse_expression();
-                self.advance_specific(&Token::RParen);
-                expression
-            }
+    fn parse_unary(&mut self) -> Result<Expr, SaltError> {
+        let start = self.peek_span();
+        match self.peek() {
             Token::Minus => {
-                let unary = self.parse_unary();
+                self.advance()?;
+                let unary = self.parse_unary()?;
+                let span = self.span_from(start);
                 let negate = UnaryOp {
                     op_type: UnaryOpType::Negate,
                     expr: Box::new(unary),
+                    span,
+                };
+                Ok(Expr::UnaryOp(negate))
+            }
+            Token::Bang => {
+                self.advance()?;
+                let unary = self.parse_unary()?;
+                let span = self.span_from(start);
+                let not = UnaryOp {
+                    op_type: UnaryOpType::Not,
+                    expr: Box::new(unary),
+                    span,
+                };
+                Ok(Expr::UnaryOp(not))
+            }
+            Token::Match => {
+                let match_expr = self.parse_match(Self::parse_nested_expression)?;
+                Ok(Expr::Match(match_expr))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, SaltError> {
+        let mut current = self.parse_primary()?;
+        loop {
+            match self.peek() {
+                Token::LSquare => {
+                    self.advance()?;
+                    let index = self.parse_nested_expression()?;
+                    self.advance_specific(&Token::RSquare)?;
+                    let span = self.span_from(current.span());
+                    current = Expr::Index(Index {
+                        target: Box::new(current),
+                        index: Box::new(index),
+                        span,
+                    });
+                }
+                Token::LParen => {
+                    let arguments = self.parse_arguments()?;
+                    let span = self.span_from(current.span());
+                    current = Expr::Call(Call {
+                        callee: Box::new(current),
+                        arguments,
+                        span,
+                    });
+                }
+                Token::Dot => {
+                    self.advance()?;
+                    let field_span = self.peek_span();
+                    let field = Name::new(self.parse_name()?, field_span);
+                    let span = self.span_from(current.span());
+                    current = match current {
+                        // A type of an imported module, as in `shapes.Point { .. }`.
+                        Expr::Name(module) if self.peek() == &Token::ColonColon => {
+                            let start = module.span;
+                            self.parse_enum_literal(Some(module), field.name, start)?
+                        }
+                        Expr::Name(module)
+                            if self.peek() == &Token::LCurly && self.struct_literals =>
+                        {
+                            let start = module.span;
+                            self.parse_struct_literal(Some(module), field.name, start)?
+                        }
+                        target => Expr::Field(Field {
+                            target: Box::new(target),
+                            field,
+                            span,
+                        }),
+                    };
+                }
+                _ => return Ok(current),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SaltError> {
+        let start = self.peek_span();
+        match self.advance()? {
+            Token::LParen => {
+                let expression = self.parse_nested_expression()?;
+                self.advance_specific(&Token::RParen)?;
+                Ok(expression)
+            }
+            Token::Function => {
+                let parameters = self.parse_parameters()?;
+                let span = self.span_from(start);
+                // `break` and `continue` cannot reach a loop outside the
+                // function body.
+                let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                let block = self.parse_block();
+                self.loop_depth = loop_depth;
+                let function = Function {
+                    name: "<lambda>".to_string(),
+                    parameters,
+                    block: block?,
+                    span,
+                    slots: 0,
+                    captures: Vec::new(),
                 };
-                Expr::UnaryOp(negate)
+                Ok(Expr::Lambda(Lambda {
+                    function: Rc::new(function),
+                    span: self.span_from(start),
+                }))
+            }
+            Token::LSquare => {
+                let elements = self.parse_elements()?;
+                let span = self.span_from(start);
+                Ok(Expr::Array(Array { elements, span }))
+            }
+            Token::LCurly => {
+                let entries = self.parse_entries()?;
+                let span = self.span_from(start);
+                Ok(Expr::Map(MapLiteral { entries, span }))
             }
-            Token::Integer(integer) => Expr::Literal(Value::Integer(*integer)),
+            Token::Integer(integer) => Ok(Expr::Literal(Literal {
+                value: Value::Integer(*integer),
+                span: start,
+            })),
+            Token::BigInteger(integer) => Ok(Expr::Literal(Literal {
+                value: Value::BigInt(Rc::new(integer.clone())),
+                span: start,
+            })),
             Token::Name(name) => {
                 let n = name.to_string();
-                self.parse_name_or_function(n)
+                self.parse_name_or_function(n, start)
             }
             Token::Time => {
-                self.advance_specific(&Token::LParen);
-                self.advance_specific(&Token::RParen);
-                let time = Time {};
-                Expr::Time(time)
+                self.advance_specific(&Token::LParen)?;
+                self.advance_specific(&Token::RParen)?;
+                let span = self.span_from(start);
+                let time = Time { span };
+                Ok(Expr::Time(time))
             }
-            Token::True => Expr::Literal(Value::Boolean(true)),
-            Token::False => Expr::Literal(Value::Boolean(false)),
-            other => panic!("Error while trying to parse expression: {other:?}"),
+            Token::Float(float) => Ok(Expr::Literal(Literal {
+                value: Value::Float(*float),
+                span: start,
+            })),
+            Token::String(string) => Ok(Expr::Literal(Literal {
+                value: Value::String(string.as_str().into()),
+                span: start,
+            })),
+            Token::True => Ok(Expr::Literal(Literal {
+                value: Value::Boolean(true),
+                span: start,
+            })),
+            Token::False => Ok(Expr::Literal(Literal {
+                value: Value::Boolean(false),
+                span: start,
+            })),
+            other => Err(SaltError::parse(
+                format!("Expected an expression but found {other}"),
+                start,
+            )),
         }
     }
 
-    fn parse_name_or_function(&mut self, name: String) -> Expr {
+    fn parse_name_or_function(&mut self, name: String, start: Span) -> Result<Expr, SaltError> {
         match self.peek() {
-            Token::LParen => {
-                let arguments = self.parse_arguments();
-                let call = Call { name, arguments };
-                Expr::Call(call)
-            }
-            _ => Expr::Name(name),
+            Token::ColonColon => self.parse_enum_literal(None, name, start),
+            Token::LCurly if self.struct_literals => self.parse_struct_literal(None, name, start),
+            _ => Ok(Expr::Name(Name::new(name, start))),
         }
     }
 
-    fn parse_arguments(&mut self) -> Vec<Expr> {
-        self.advance_specific(&Token::LParen);
+    /// Parses `::Variant(arguments...)` after the enum name, which started
+    /// at `start` with its module, if any.
+    fn parse_enum_literal(
+        &mut self,
+        module: Option<Name>,
+        enum_name: String,
+        start: Span,
+    ) -> Result<Expr, SaltError> {
+        self.advance_specific(&Token::ColonColon)?;
+        let variant_span = self.peek_span();
+        let variant = Name::new(self.parse_name()?, variant_span);
+        let arguments = match self.peek() {
+            Token::LParen => self.parse_arguments()?,
+            _ => Vec::new(),
+        };
+        let span = self.span_from(start);
+        Ok(Expr::EnumLiteral(Box::new(EnumLiteral {
+            module,
+            enum_name,
+            variant,
+            arguments,
+            span,
+        })))
+    }
+
+    /// Parses `{ fields... }` after the struct name, like
+    /// `parse_enum_literal`.
+    fn parse_struct_literal(
+        &mut self,
+        module: Option<Name>,
+        name: String,
+        start: Span,
+    ) -> Result<Expr, SaltError> {
+        self.advance_specific(&Token::LCurly)?;
+        let fields = self.parse_field_initializers()?;
+        let span = self.span_from(start);
+        Ok(Expr::StructLiteral(StructLiteral {
+            module,
+            name,
+            fields,
+            span,
+        }))
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Expr>, SaltError> {
+        self.advance_specific(&Token::LParen)?;
         let mut arguments = Vec::new();
         if self.peek() == &Token::RParen {
-            self.advance();
-            return arguments;
+            self.advance()?;
+            return Ok(arguments);
         }
-        let first_argument = self.parse_expression();
+        let first_argument = self.parse_nested_expression()?;
         arguments.push(first_argument);
 
         loop {
-            match self.advance() {
-                Token::RParen => return arguments,
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RParen => return Ok(arguments),
                 Token::Comma => (),
-                other => panic!("Error while trying to parse arguments: {other:?}"),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `)` in argument list but found {other}"),
+                        span,
+                    ))
+                }
             }
-            let argument = self.parse_expression();
+            let argument = self.parse_nested_expression()?;
             arguments.push(argument);
         }
     }
 
+    /// Parses the elements of an array literal after its opening `[`,
+    /// allowing a trailing comma.
+    fn parse_elements(&mut self) -> Result<Vec<Expr>, SaltError> {
+        let mut elements = Vec::new();
+        loop {
+            if self.peek() == &Token::RSquare {
+                self.advance()?;
+                return Ok(elements);
+            }
+            elements.push(self.parse_nested_expression()?);
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RSquare => return Ok(elements),
+                Token::Comma => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `]` in array literal but found {other}"),
+                        span,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses the `field: value` pairs of a struct literal after its opening
+    /// `{`, allowing a trailing comma.
+    fn parse_field_initializers(&mut self) -> Result<Vec<(Name, Expr)>, SaltError> {
+        let mut fields = Vec::new();
+        loop {
+            if self.peek() == &Token::RCurly {
+                self.advance()?;
+                return Ok(fields);
+            }
+            let span = self.peek_span();
+            let name = self.parse_name()?;
+            self.advance_specific(&Token::Colon)?;
+            let value = self.parse_nested_expression()?;
+            fields.push((Name::new(name, span), value));
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RCurly => return Ok(fields),
+                Token::Comma => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `}}` in struct literal but found {other}"),
+                        span,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses the `key: value` entries of a map literal after its opening
+    /// `{`, allowing a trailing comma.
+    fn parse_entries(&mut self) -> Result<Vec<(Expr, Expr)>, SaltError> {
+        let mut entries = Vec::new();
+        loop {
+            if self.peek() == &Token::RCurly {
+                self.advance()?;
+                return Ok(entries);
+            }
+            let key = self.parse_nested_expression()?;
+            self.advance_specific(&Token::Colon)?;
+            let value = self.parse_nested_expression()?;
+            entries.push((key, value));
+            let span = self.peek_span();
+            match self.advance()? {
+                Token::RCurly => return Ok(entries),
+                Token::Comma => (),
+                other => {
+                    return Err(SaltError::parse(
+                        format!("Expected `,` or `}}` in map literal but found {other}"),
+                        span,
+                    ))
+                }
+            }
+        }
+    }
+
     fn has_ended(&mut self) -> bool {
-        matches!(self.tokens.peek(), None)
+        self.peek() == &Token::End
+    }
+
+    fn peek_spanned(&mut self) -> &'a SpannedToken {
+        // The lexer always terminates the stream with `Token::End`, which is
+        // never consumed.
+        self.tokens
+            .peek()
+            .copied()
+            .expect("Missing end of file token")
     }
 
-    fn peek(&mut self) -> &Token {
-        match self.tokens.peek() {
-            Some(token) => token,
-            None => panic!("Unexpected end of file"),
+    fn peek(&mut self) -> &'a Token {
+        &self.peek_spanned().token
+    }
+
+    fn peek_span(&mut self) -> Span {
+        self.peek_spanned().span
+    }
+
+    /// Returns the span from `start` to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        match self.previous {
+            Some(previous) => start.to(previous.span),
+            None => start,
         }
     }
 
-    fn advance(&mut self) -> &Token {
-        match self.tokens.next() {
-            Some(token) => token,
-            None => panic!("Unexpected end of file"),
+    fn advance(&mut self) -> Result<&'a Token, SaltError> {
+        let spanned = self.peek_spanned();
+        if spanned.token == Token::End {
+            return Err(SaltError::parse("Unexpected end of file", spanned.span));
         }
+        self.tokens.next();
+        self.previous = Some(spanned);
+        Ok(&spanned.token)
     }
 
-    fn advance_specific(&mut self, expected: &Token) {
-        match self.tokens.next() {
-            Some(token) if token == expected => (),
-            Some(other) => panic!("Expected {expected:?} but found {other:?}"),
-            None => panic!("Unexpected end of file (expected {expected:?}"),
+    fn advance_specific(&mut self, expected: &Token) -> Result<(), SaltError> {
+        let spanned = self.peek_spanned();
+        if &spanned.token != expected {
+            let message = match &spanned.token {
+                Token::End => format!("Unexpected end of file (expected {expected})"),
+                other => format!("Expected {expected} but found {other}"),
+            };
+            return Err(SaltError::parse(message, spanned.span));
         }
+        self.tokens.next();
+        self.previous = Some(spanned);
+        Ok(())
     }
 }
@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::ast::{
+    Assignment, Block, Call, Capture, Else, Expr, Field, Function, Global, IfStmt, Iterable, Match,
+    Name, Pattern, Program, Resolution, Statement, Target,
+};
+use crate::builtins;
+use crate::error::{ErrorKind, SaltError};
+use crate::span::Span;
+
+/// Resolves every variable of a parsed program before it runs. Local
+/// variables get a numeric slot in the frame of their function, numbered
+/// from zero with the parameters first; slots are never reused within a
+/// function, so a slot a lambda captures from cannot change meaning. Other
+/// names must be a global, function or builtin of the module, or the module
+/// of an import before a `.`, and are otherwise reported as errors.
+/// Lambdas capture a copy of each variable they use when they are created,
+/// so assigning to a captured variable inside a lambda is an error too, as
+/// is assigning to a constant of the module or of one of the `imports`,
+/// given by the name they are imported as.
+pub fn resolve(program: &mut Program, imports: &HashMap<&str, &Program>) -> Vec<SaltError> {
+    let mut resolver = Resolver::default();
+    for (module, imported) in imports {
+        for global in &imported.globals {
+            if let Global::Variable(variable) = global {
+                if variable.constant {
+                    let key = (module.to_string(), variable.name.clone());
+                    resolver.imported_constants.insert(key, variable.span);
+                }
+            }
+        }
+    }
+    for global in &program.globals {
+        match global {
+            Global::Function(function) => {
+                resolver.functions.insert(function.name.clone());
+            }
+            Global::Variable(variable) => {
+                resolver.variables.insert(variable.name.clone());
+                if variable.constant {
+                    resolver
+                        .constants
+                        .insert(variable.name.clone(), variable.span);
+                }
+            }
+            Global::Import(import) => {
+                resolver.imports.insert(import.name.clone());
+            }
+            Global::Struct(_) | Global::Enum(_) => (),
+        }
+    }
+    for global in &mut program.globals {
+        match global {
+            Global::Function(function) => resolver.resolve_function(function),
+            Global::Variable(variable) => {
+                resolver.frames.push(Frame::default());
+                resolver.resolve_expr(&mut variable.expr);
+                variable.slots = resolver.frames.pop().expect("a frame was pushed").slots;
+            }
+            Global::Struct(_) | Global::Enum(_) | Global::Import(_) => (),
+        }
+    }
+    resolver.errors
+}
+
+/// The locals of a function being resolved.
+#[derive(Default)]
+struct Frame {
+    /// The visible local variables with their slots, innermost last.
+    locals: Vec<(String, usize)>,
+    /// Variables captured from the enclosing functions.
+    captured: Vec<(String, Capture)>,
+    slots: usize,
+}
+
+#[derive(Default)]
+struct Resolver {
+    variables: HashSet<String>,
+    /// The constants among `variables`, with their definitions.
+    constants: HashMap<String, Span>,
+    functions: HashSet<String>,
+    imports: HashSet<String>,
+    /// The constants of the imported modules, by import name and constant
+    /// name, with their definitions.
+    imported_constants: HashMap<(String, String), Span>,
+    /// The functions being resolved, with lambdas inside the function
+    /// creating them.
+    frames: Vec<Frame>,
+    errors: Vec<SaltError>,
+}
+
+impl Resolver {
+    fn resolve_function(&mut self, function: &mut Function) {
+        self.frames.push(Frame::default());
+        for parameter in &function.parameters {
+            self.declare(parameter);
+        }
+        self.resolve_block(&mut function.block);
+        let frame = self.frames.pop().expect("a frame was pushed");
+        function.slots = frame.slots;
+        function.captures = frame
+            .captured
+            .into_iter()
+            .map(|(_, capture)| capture)
+            .collect();
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("a function is being resolved")
+    }
+
+    fn declare(&mut self, name: &str) -> usize {
+        let frame = self.frame();
+        let slot = frame.slots;
+        frame.slots += 1;
+        frame.locals.push((name.to_string(), slot));
+        slot
+    }
+
+    /// Resolves `resolve` in a new scope, dropping the locals it declares.
+    fn scoped(&mut self, resolve: impl FnOnce(&mut Self)) {
+        let visible = self.frame().locals.len();
+        resolve(self);
+        self.frame().locals.truncate(visible);
+    }
+
+    /// Finds a variable of the function at `level`, capturing it from the
+    /// enclosing functions if necessary.
+    fn resolve_local(&mut self, level: usize, name: &str) -> Option<usize> {
+        let frame = &self.frames[level];
+        let local = frame.locals.iter().rev().find(|(local, _)| local == name);
+        if let Some((_, slot)) = local {
+            return Some(*slot);
+        }
+        let captured = frame.captured.iter().find(|(captured, _)| captured == name);
+        if let Some((_, capture)) = captured {
+            return Some(capture.to);
+        }
+        if level == 0 {
+            return None;
+        }
+        let from = self.resolve_local(level - 1, name)?;
+        let frame = &mut self.frames[level];
+        let to = frame.slots;
+        frame.slots += 1;
+        frame
+            .captured
+            .push((name.to_string(), Capture { from, to }));
+        Some(to)
+    }
+
+    /// Resolves `name` to a local variable or a definition of the module.
+    /// Returns `false` if it refers to nothing.
+    fn lookup(&mut self, name: &mut Name) -> bool {
+        let level = self.frames.len() - 1;
+        if let Some(slot) = self.resolve_local(level, &name.name) {
+            name.resolution = Resolution::Local(slot);
+        } else if self.variables.contains(&name.name)
+            || self.functions.contains(&name.name)
+            || builtins::lookup(&name.name).is_some()
+        {
+            name.resolution = Resolution::Global;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn resolve_block(&mut self, block: &mut Block) {
+        self.scoped(|resolver| {
+            for statement in &mut block.statements {
+                resolver.resolve_statement(statement);
+            }
+        });
+    }
+
+    fn resolve_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Let(let_stmt) => {
+                self.resolve_expr(&mut let_stmt.expr);
+                let_stmt.slot = self.declare(&let_stmt.name);
+            }
+            Statement::If(if_stmt) => self.resolve_if(if_stmt),
+            Statement::While(while_loop) => {
+                self.resolve_expr(&mut while_loop.condition);
+                self.resolve_block(&mut while_loop.body);
+            }
+            Statement::For(for_loop) => {
+                match &mut for_loop.iterable {
+                    Iterable::Range(range) => {
+                        self.resolve_expr(&mut range.start);
+                        self.resolve_expr(&mut range.end);
+                        if let Some(step) = &mut range.step {
+                            self.resolve_expr(step);
+                        }
+                    }
+                    Iterable::Expr(expr) => self.resolve_expr(expr),
+                }
+                self.scoped(|resolver| {
+                    for_loop.slot = resolver.declare(&for_loop.variable);
+                    resolver.resolve_block(&mut for_loop.body);
+                });
+            }
+            Statement::Return(return_stmt) => self.resolve_expr(&mut return_stmt.expr),
+            Statement::Expr(expr) => self.resolve_expr(expr),
+            Statement::Assignment(assignment) => self.resolve_assignment(assignment),
+            Statement::Print(print) => self.resolve_expr(&mut print.expr),
+            Statement::Break(_) | Statement::Continue(_) => (),
+            Statement::Match(match_stmt) => self.resolve_match(match_stmt, Self::resolve_block),
+        }
+    }
+
+    fn resolve_if(&mut self, if_stmt: &mut IfStmt) {
+        self.resolve_expr(&mut if_stmt.condition);
+        self.resolve_block(&mut if_stmt.body);
+        match &mut if_stmt.else_branch {
+            Some(Else::Block(block)) => self.resolve_block(block),
+            Some(Else::If(else_if)) => self.resolve_if(else_if),
+            None => (),
+        }
+    }
+
+    fn resolve_assignment(&mut self, assignment: &mut Assignment) {
+        self.resolve_expr(&mut assignment.expr);
+        match &mut assignment.target {
+            Target::Name(name) => {
+                let level = self.frames.len() - 1;
+                let locals = &self.frames[level].locals;
+                let is_local = locals.iter().any(|(local, _)| *local == name.name);
+                if let Some(slot) = self.resolve_local(level, &name.name) {
+                    if !is_local {
+                        let error = resolve_error(
+                            format!("Cannot assign to captured variable `{}`", name.name),
+                            assignment.span,
+                        )
+                        .with_note(
+                            format!(
+                                "lambdas capture a copy of `{}` when they are created",
+                                name.name
+                            ),
+                            None,
+                        );
+                        self.errors.push(error);
+                    }
+                    name.resolution = Resolution::Local(slot);
+                } else if self.variables.contains(&name.name) {
+                    name.resolution = Resolution::Global;
+                    if let Some(&definition) = self.constants.get(&name.name) {
+                        let error = constant_assignment(&name.name, assignment.span, definition);
+                        self.errors.push(error);
+                    }
+                } else {
+                    let error = resolve_error(
+                        format!("Cannot assign to undeclared variable `{}`", name.name),
+                        assignment.span,
+                    )
+                    .with_note(
+                        format!("declare it first with `let {} = ...;`", name.name),
+                        None,
+                    );
+                    self.errors.push(error);
+                }
+            }
+            Target::Index(index) => {
+                self.resolve_expr(&mut index.target);
+                self.resolve_expr(&mut index.index);
+            }
+            Target::Field(field) => {
+                self.resolve_field(field);
+                let Expr::Name(module) = field.target.as_ref() else {
+                    return;
+                };
+                if module.resolution != Resolution::Import {
+                    return;
+                }
+                let key = (module.name.clone(), field.field.name.clone());
+                if let Some(&definition) = self.imported_constants.get(&key) {
+                    let error = constant_assignment(&field.field.name, assignment.span, definition);
+                    self.errors.push(error);
+                }
+            }
+        }
+    }
+
+    /// Resolves the scrutinee, then each arm in a new scope holding the
+    /// pattern's bindings.
+    fn resolve_match<T>(&mut self, match_: &mut Match<T>, resolve_body: fn(&mut Self, &mut T)) {
+        self.resolve_expr(&mut match_.scrutinee);
+        for arm in &mut match_.arms {
+            self.scoped(|resolver| {
+                resolver.resolve_pattern(&mut arm.pattern);
+                resolve_body(resolver, &mut arm.body);
+            });
+        }
+    }
+
+    fn resolve_pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Binding(name) => {
+                name.resolution = Resolution::Local(self.declare(&name.name));
+            }
+            Pattern::Variant(variant) => {
+                for field in &mut variant.fields {
+                    self.resolve_pattern(field);
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(_) => (),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Literal(_) | Expr::Time(_) => (),
+            Expr::Name(name) => {
+                if !self.lookup(name) {
+                    let error =
+                        resolve_error(format!("No such variable: {}", name.name), name.span);
+                    self.errors.push(error);
+                }
+            }
+            Expr::Call(call) => self.resolve_call(call),
+            Expr::Lambda(lambda) => {
+                let function = Rc::get_mut(&mut lambda.function)
+                    .expect("lambdas are not shared before they are resolved");
+                self.resolve_function(function);
+            }
+            Expr::Array(array) => self.resolve_exprs(&mut array.elements),
+            Expr::Map(map) => {
+                for (key, value) in &mut map.entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::StructLiteral(literal) => {
+                for (_, value) in &mut literal.fields {
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::EnumLiteral(literal) => self.resolve_exprs(&mut literal.arguments),
+            Expr::Index(index) => {
+                self.resolve_expr(&mut index.target);
+                self.resolve_expr(&mut index.index);
+            }
+            Expr::Field(field) => self.resolve_field(field),
+            Expr::UnaryOp(unary_op) => self.resolve_expr(&mut unary_op.expr),
+            Expr::BinaryOp(binary_op) => {
+                self.resolve_expr(&mut binary_op.left);
+                self.resolve_expr(&mut binary_op.right);
+            }
+            Expr::Match(match_expr) => self.resolve_match(match_expr, Self::resolve_expr),
+        }
+    }
+
+    fn resolve_exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.resolve_expr(expr);
+        }
+    }
+
+    fn resolve_call(&mut self, call: &mut Call) {
+        match call.callee.as_mut() {
+            Expr::Name(name) => {
+                if !self.lookup(name) {
+                    let error =
+                        resolve_error(format!("No such function: {}", name.name), call.span);
+                    self.errors.push(error);
+                }
+            }
+            callee => self.resolve_expr(callee),
+        }
+        self.resolve_exprs(&mut call.arguments);
+    }
+
+    /// Resolves the target of a field access, which may also name an
+    /// imported module.
+    fn resolve_field(&mut self, field: &mut Field) {
+        if let Expr::Name(name) = field.target.as_mut() {
+            if !self.lookup(name) && self.imports.contains(&name.name) {
+                name.resolution = Resolution::Import;
+                return;
+            }
+        }
+        self.resolve_expr(&mut field.target);
+    }
+}
+
+fn resolve_error(message: impl Into<String>, span: Span) -> SaltError {
+    SaltError::new(ErrorKind::Check, message, Some(span))
+}
+
+/// The error for assigning to the constant `name` defined at `definition`.
+fn constant_assignment(name: &str, span: Span, definition: Span) -> SaltError {
+    resolve_error(format!("Cannot assign to constant `{name}`"), span)
+        .with_note(format!("constant `{name}` defined here"), Some(definition))
+}
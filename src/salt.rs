@@ -1,19 +1,116 @@
-use crate::{interpreter::Interpeter, lexer::Lexer, parser::Parser, value::Value};
+use std::collections::HashMap;
+#[cfg(test)]
+use std::path::Path;
 
-pub struct Salt {}
+#[cfg(test)]
+use crate::module::Loader;
+use crate::{
+    ast::Program, checker, compiler, error::SaltError, interpreter::Interpeter, lexer::Lexer,
+    module::Module, optimizer, parser::Parser, resolver, value::Value, vm::Vm,
+};
+
+/// How programs are executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Interpret the syntax tree directly.
+    TreeWalker,
+    /// Compile to bytecode and run it on a virtual machine.
+    Vm,
+}
+
+pub struct Salt {
+    backend: Backend,
+    optimize: bool,
+}
 
 impl Salt {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            backend: Backend::TreeWalker,
+            optimize: false,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Whether to run the optimizer on programs before executing them.
+    pub fn with_optimizer(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Parses and executes `source`. Returns every error found before the
+    /// program runs, or else the error that stopped it. Imports are resolved
+    /// relative to the current directory.
+    #[cfg(test)]
+    pub fn run(&self, source: &str) -> Result<Value, Vec<SaltError>> {
+        let modules = Loader::new().load_source(Path::new("<source>"), source.to_string())?;
+        self.execute(modules).map_err(|error| vec![error])
+    }
+
+    /// Like `run`, for the program starting at the file `path`.
+    #[cfg(test)]
+    pub fn run_file(&self, path: &Path) -> Result<Value, Vec<SaltError>> {
+        let modules = Loader::new().load_file(path)?;
+        self.execute(modules).map_err(|error| vec![error])
+    }
+
+    /// Lexes, parses, checks and resolves `source`, which imports nothing,
+    /// returning every error found rather than stopping at the first one.
+    /// When there are syntax errors, the program holds the parts that
+    /// parsed, and is not checked, since the parts that did not parse would
+    /// look missing.
+    #[cfg(test)]
+    pub fn parse(&self, source: &str) -> (Program, Vec<SaltError>) {
+        let (mut program, errors) = self.parse_file(source, 0);
+        if !errors.is_empty() {
+            return (program, errors);
+        }
+        let errors = self.check(&mut program, &HashMap::new());
+        (program, errors)
+    }
+
+    /// Lexes and parses `source`, the source file with index `file`,
+    /// returning every syntax error and the parts that parsed.
+    pub fn parse_file(&self, source: &str, file: usize) -> (Program, Vec<SaltError>) {
+        let mut lexer = Lexer::new(source.chars()).with_file(file);
+        let tokens = match lexer.lex() {
+            Ok(tokens) => tokens,
+            Err(error) => return (Program::default(), vec![error]),
+        };
+        Parser::new(tokens.iter()).parse()
+    }
+
+    /// Checks and resolves a parsed program, given the programs of the
+    /// modules it imports by the name they are imported as.
+    pub fn check(
+        &self,
+        program: &mut Program,
+        imports: &HashMap<&str, &Program>,
+    ) -> Vec<SaltError> {
+        let mut errors = checker::check(program, imports);
+        errors.extend(resolver::resolve(program, imports));
+        errors
     }
 
-    pub fn run(&self, source: &str) -> Value {
-        let mut lexer = Lexer::new(source.chars());
-        let tokens = lexer.lex();
-        let mut parser = Parser::new(tokens.iter());
-        let ast = parser.parse();
+    /// Runs `main` in the last of `modules`, after evaluating the globals of
+    /// every module in order.
+    pub fn execute(&self, mut modules: Vec<Module>) -> Result<Value, SaltError> {
+        if self.optimize {
+            for module in &mut modules {
+                optimizer::optimize(&mut module.program);
+            }
+        }
+        if self.backend == Backend::Vm {
+            return Vm::new(compiler::compile(&modules)).run();
+        }
         let mut interpreter = Interpeter::new();
-        interpreter.load(ast);
+        for module in modules {
+            interpreter.load(module)?;
+        }
         interpreter.call_function("main")
     }
 }
@@ -0,0 +1,15 @@
+/// A source file and the name it is reported under in diagnostics.
+/// `Span::file` is an index into the list of files of a program.
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// A range of source text, as a byte offset plus the 1-based line and column
+/// of its first character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Index of the source file, `0` for the file a program starts from.
+    pub file: usize,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, line: usize, column: usize, length: usize) -> Self {
+        Self {
+            file: 0,
+            offset,
+            line,
+            column,
+            length,
+        }
+    }
+
+    /// Returns a span starting at `self` and ending where `end` ends.
+    pub fn to(self, end: Span) -> Span {
+        let length = (end.offset + end.length).saturating_sub(self.offset);
+        Span { length, ..self }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -1,43 +1,238 @@
+/// Runs programs on both the tree-walking interpreter and the VM, with and
+/// without the optimizer, checking that they all produce the same value or
+/// the same error.
+#[cfg(test)]
+struct BothBackends;
+
+#[cfg(test)]
+impl BothBackends {
+    fn new() -> Self {
+        Self
+    }
+
+    /// Runs `source`, which must fail with a single error if it fails.
+    fn run(&self, source: &str) -> Result<crate::value::Value, crate::error::SaltError> {
+        Self::agree(|salt| salt.run(source)).map_err(Self::single)
+    }
+
+    fn run_file(
+        &self,
+        path: &std::path::Path,
+    ) -> Result<crate::value::Value, crate::error::SaltError> {
+        Self::agree(|salt| salt.run_file(path)).map_err(Self::single)
+    }
+
+    /// Parsing does not depend on the backend.
+    fn parse(&self, source: &str) -> Result<crate::ast::Program, Vec<crate::error::SaltError>> {
+        match crate::salt::Salt::new().parse(source) {
+            (program, errors) if errors.is_empty() => Ok(program),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    fn single(mut errors: Vec<crate::error::SaltError>) -> crate::error::SaltError {
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        errors.remove(0)
+    }
+
+    fn agree(
+        run: impl Fn(&crate::salt::Salt) -> Result<crate::value::Value, Vec<crate::error::SaltError>>,
+    ) -> Result<crate::value::Value, Vec<crate::error::SaltError>> {
+        use crate::salt::{Backend, Salt};
+        let tree_walker = run(&Salt::new());
+        let others = [
+            Salt::new().with_backend(Backend::Vm),
+            Salt::new().with_optimizer(true),
+            Salt::new().with_backend(Backend::Vm).with_optimizer(true),
+        ];
+        for salt in &others {
+            let other = run(salt);
+            match (&tree_walker, &other) {
+                // Function values differ between the backends but print the
+                // same.
+                (Ok(expected), Ok(actual)) => {
+                    assert_eq!(expected.to_string(), actual.to_string())
+                }
+                _ => assert_eq!(tree_walker, other),
+            }
+        }
+        tree_walker
+    }
+}
+
 #[cfg(test)]
 mod lexer_tests {
-    /*#[test]
-    fn test0() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
-    }*/
+    use crate::{lexer::Lexer, span::Span, token::Token};
+
+    #[test]
+    fn spans() {
+        let source = "fn main() {\n    # comment\n    x = 12;\n}";
+        let mut lexer = Lexer::new(source.chars());
+        let tokens = lexer.lex().unwrap();
+        let x = &tokens[5];
+        assert_eq!(x.token, Token::Name("x".to_string()));
+        assert_eq!(x.span, Span::new(30, 3, 5, 1));
+        let twelve = &tokens[7];
+        assert_eq!(twelve.token, Token::Integer(12));
+        assert_eq!(twelve.span, Span::new(34, 3, 9, 2));
+        let end = tokens.last().unwrap();
+        assert_eq!(end.token, Token::End);
+        assert_eq!(end.span.line, 4);
+    }
+
+    #[test]
+    fn numbers() {
+        let source = "1..2 3.25 1e-9 2.5E3 7";
+        let tokens: Vec<Token> = Lexer::new(source.chars())
+            .lex()
+            .unwrap()
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Integer(1),
+                Token::DotDot,
+                Token::Integer(2),
+                Token::Float(3.25),
+                Token::Float(1e-9),
+                Token::Float(2500.0),
+                Token::Integer(7),
+                Token::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        let source = r#""a\tb\n\"c\" \\ \u{1F600}""#;
+        let tokens = Lexer::new(source.chars()).lex().unwrap();
+        assert_eq!(
+            tokens[0].token,
+            Token::String("a\tb\n\"c\" \\ \u{1F600}".to_string())
+        );
+
+        let error = Lexer::new(r#""\q""#.chars()).lex().unwrap_err();
+        assert_eq!(error.message, "Invalid escape sequence");
+        let error = Lexer::new(r#""abc"#.chars()).lex().unwrap_err();
+        assert_eq!(error.message, "Unterminated string literal");
+    }
 }
 
 #[cfg(test)]
 mod parser_tests {
-    /*#[test]
-    fn test0() {
-        assert_eq!(1, 1);
-    }*/
+    use crate::{ast::Global, lexer::Lexer, parser::Parser};
+
+    #[test]
+    fn error_recovery() {
+        let source = "
+        fn main() {
+            x = ;
+            y = 2
+            if x {
+                z = (1 + 2;
+                w = 3;
+            }
+            print(1);
+        }
+
+        fn broken(a b) {
+            return 1;
+        }
+
+        fn ok() {
+            return 2;
+        }
+        ";
+        let tokens = Lexer::new(source.chars()).lex().unwrap();
+        let (program, errors) = Parser::new(tokens.iter()).parse();
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.unwrap().line).collect();
+        assert_eq!(lines, vec![3, 5, 6, 12]);
+
+        let names: Vec<&str> = program
+            .globals
+            .iter()
+            .map(|global| match global {
+                Global::Function(function) => function.name.as_str(),
+                Global::Struct(definition) => definition.name.as_str(),
+                Global::Enum(definition) => definition.name.as_str(),
+                Global::Variable(variable) => variable.name.as_str(),
+                Global::Import(import) => import.name.as_str(),
+            })
+            .collect();
+        assert_eq!(names, vec!["main", "ok"]);
+    }
+
+    #[test]
+    fn break_outside_loop() {
+        let source = "
+        fn main() {
+            while true {
+                break;
+            }
+            if true {
+                continue;
+            }
+        }
+        ";
+        let tokens = Lexer::new(source.chars()).lex().unwrap();
+        let (_, errors) = Parser::new(tokens.iter()).parse();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "`continue` outside of a loop");
+        assert_eq!(errors[0].span.unwrap().line, 7);
+    }
+
+    #[test]
+    fn struct_literals_in_headers() {
+        let source = "
+        fn main() {
+            if p.x == (Point { x: 1 }).x {
+                return p;
+            }
+            for x in xs {}
+            while done {}
+            if Point { x: 1 }.x == 1 {}
+        }
+        ";
+        let tokens = Lexer::new(source.chars()).lex().unwrap();
+        let (_, errors) = Parser::new(tokens.iter()).parse();
+        // Only the last `if` fails, where `Point {` opens the body.
+        assert_eq!(
+            errors[0].message,
+            "Expected `;` or `=` after expression but found `:`"
+        );
+        assert_eq!(errors[0].span.unwrap().line, 8);
+    }
 }
 
 #[cfg(test)]
 mod interpreter_tests {
-    use crate::{salt::Salt, value::Value};
+    use super::BothBackends;
+    use crate::value::Value;
 
     #[test]
     fn math() {
-        let salt = Salt::new();
-        let value = salt.run(
-            "
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
         fn main() {
-            result = 4 * 5 + 12 / (10 - 15 % 8);
+            let result = 4 * 5 + 12 / (10 - 15 % 8);
             return result;
         }
         ",
-        );
+            )
+            .unwrap();
         assert_eq!(value, Value::Integer(24));
     }
 
     #[test]
     fn if_stmt() {
-        let salt = Salt::new();
-        let value = salt.run(
-            "
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
         fn main() {
             if 2 >= 3 {
                 return 1;
@@ -50,18 +245,482 @@ mod interpreter_tests {
             }
         }
         ",
-        );
+            )
+            .unwrap();
         assert_eq!(value, Value::Integer(3));
     }
 
+    #[test]
+    fn else_if() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            return classify(-5) * 100 + classify(0) * 10 + classify(7);
+        }
+
+        fn classify(n) {
+            if n < 0 {
+                return 1;
+            } else if n == 0 {
+                return 2;
+            } else {
+                return 3;
+            }
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(123));
+    }
+
+    #[test]
+    fn logical_operators() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let j = 5;
+            let n = 3;
+            if j < n && explode() {
+                return 0;
+            }
+            if !(j < n) || explode() {
+                return true || false && false && !false;
+            }
+            return 1;
+        }
+
+        fn explode() {
+            return 1 + true;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Boolean(true));
+    }
+
+    #[test]
+    fn equality_and_ordering() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            return (true == true) && (true != false) && (nothing() == nothing())
+                && (1 != true) && (nothing() != false) && (false < true) && (2 >= 2);
+        }
+
+        fn nothing() {}
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Boolean(true));
+
+        let error = salt
+            .run(
+                "
+        fn main() {
+            return 1 < true;
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Type error: cannot order Integer and Boolean"
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                r#"
+        fn main() {
+            let greeting = "Hello, " + "world";
+            print(greeting);
+            if greeting == "Hello, world" && "abc" < "abd" {
+                return "count: " + 3;
+            }
+            return "";
+        }
+        "#,
+            )
+            .unwrap();
+        assert_eq!(value, Value::String("count: 3".into()));
+
+        let error = salt.run("fn main() { return \"a\" + [1]; }").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Type error: cannot apply Addition to String and Array"
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let ratio = 7 / 2.0;
+            if ratio == 3.5 && 1 == 1.0 && 2 > 1.5 && -ratio < 0 {
+                return ratio * 2 + 0.25;
+            }
+            return 0;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Float(7.25));
+        assert_eq!(Value::Float(3.0).to_string(), "3.0");
+        assert_eq!(Value::Float(1e-9).to_string(), "1e-9");
+        assert_eq!(Value::Float(0.1).to_string(), "0.1");
+    }
+
+    #[test]
+    fn big_integers() {
+        let salt = BothBackends::new();
+        let factorial = salt
+            .run(
+                "
+        fn main() {
+            let n = 1;
+            for i in 1..=30 {
+                n = n * i;
+            }
+            return n;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(factorial.to_string(), "265252859812191058636308480000000");
+
+        let run = |expr: &str| {
+            salt.run(&format!("fn main() {{ return {expr}; }}"))
+                .unwrap()
+        };
+        let big = "265252859812191058636308480000000";
+        let divisor = "12345678901234567890123";
+        assert_eq!(
+            run(&format!("{big} / {divisor}")),
+            Value::Integer(21485481838)
+        );
+        assert_eq!(
+            run(&format!("{big} % {divisor}")).to_string(),
+            "1935954455292783913926"
+        );
+        assert_eq!(
+            run(&format!("-{big} / {divisor}")),
+            Value::Integer(-21485481838)
+        );
+        assert_eq!(
+            run(&format!("-{big} % {divisor}")).to_string(),
+            "-1935954455292783913926"
+        );
+        assert_eq!(
+            run("9223372036854775807 + 1").to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            run("-(-9223372036854775807 - 1)").to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            run("(9223372036854775807 + 1) - 1"),
+            Value::Integer(i64::MAX)
+        );
+        assert_eq!(
+            run("9223372036854775808 > 9223372036854775807"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            run("-9223372036854775809 < -9223372036854775808"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            run("18446744073709551616 == 2 * 9223372036854775808"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            run("18446744073709551616 * 0.5"),
+            Value::Float(9223372036854775808.0)
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let a = [1, 2, 3,];
+            a[0] = a[1] + a[2];
+            push(a, [4]);
+            a[3][0] = 5;
+            fill(a, 10);
+            let last = pop(a);
+            return [a, last, len(a), len(\"héllo\"), []];
+        }
+
+        fn fill(a, n) {
+            while len(a) < n {
+                push(a, len(a));
+            }
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[[5, 2, 3, [5], 4, 5, 6, 7, 8], 9, 9, 5, []]"
+        );
+        assert_eq!(
+            Value::array(vec![Value::String("a, b".into())]).to_string(),
+            "[\"a, b\"]"
+        );
+        assert_eq!(
+            salt.run("fn main() { return [1, [2]] == [1, [2]]; }")
+                .unwrap(),
+            Value::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn cyclic_collections() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        struct Node { next }
+
+        fn main() {
+            let a = [];
+            push(a, a);
+            let b = [];
+            push(b, b);
+            let c = [1];
+            push(c, c);
+            let m = {};
+            m[\"self\"] = m;
+            m[\"a\"] = a;
+            let n = Node { next: 0 };
+            n.next = n;
+            let fs = [];
+            push(fs, fn() { return len(fs); });
+            return [a, m, n, a == a, a == b, a == c, n == n, fs == fs];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[[[...]], {\"self\": {...}, \"a\": [[...]]}, Node { next: Node {...} }, \
+             true, true, false, true, true]"
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let ages = { \"carol\": 35, \"alice\": 30 };
+            ages[\"bob\"] = 25;
+            ages[\"carol\"] = ages[\"carol\"] + 1;
+            let total = 0;
+            let names = [];
+            for name in ages {
+                total = total + ages[name];
+                push(names, name);
+            }
+            let mixed = { 1: [true], false: {}, };
+            return [names, total, keys(ages) == names, len(ages), contains(ages, \"bob\"),
+                contains(ages, \"dave\"), contains([1, 2], 2), mixed];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[[\"carol\", \"alice\", \"bob\"], 91, true, 3, true, false, true, {1: [true], false: {}}]"
+        );
+        assert_eq!(
+            salt.run("fn main() { return { \"a\": 1, \"b\": 2 } == { \"b\": 2, \"a\": 1 }; }")
+                .unwrap(),
+            Value::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn for_each_loop() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let a = [1, 2, 3, 4];
+            let sum = 0;
+            for x in a {
+                if x == 3 {
+                    break;
+                }
+                push(a, x);
+                sum = sum + x;
+            }
+            return [sum, len(a)];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[3, 6]");
+    }
+
+    #[test]
+    fn structs() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        struct Point { x, y }
+
+        struct Line {
+            from,
+            to,
+        }
+
+        fn main() {
+            let p = Point { y: 2, x: 1 };
+            let line = Line { from: p, to: Point { x: 3, y: 4 } };
+            p.x = 10;
+            line.to.y = line.from.x + 1;
+            shift(line.to);
+            return [line, p == Point { x: 10, y: 2 }, Point { x: \"a\", y: [] }];
+        }
+
+        fn shift(point) {
+            point.x = point.x * 2;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[Line { from: Point { x: 10, y: 2 }, to: Point { x: 6, y: 11 } }, true, \
+             Point { x: \"a\", y: [] }]"
+        );
+    }
+
+    #[test]
+    fn enums_and_match() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        enum Shape {
+            Circle(r),
+            Rect(w, h),
+            Empty,
+        }
+
+        enum Option { Some(value), None }
+
+        fn area(shape) {
+            return match shape {
+                Shape::Circle(r) => 3 * r * r,
+                Shape::Rect(w, h) => w * h,
+                Shape::Empty => 0,
+            };
+        }
+
+        fn describe(n) {
+            match n {
+                0 => {
+                    return \"zero\";
+                }
+                -1 => {
+                    return \"minus one\";
+                }
+                _ => {}
+            }
+            return \"other\";
+        }
+
+        fn main() {
+            let shapes = [Shape::Circle(2), Shape::Rect(3, 4), Shape::Empty];
+            let areas = [];
+            for shape in shapes {
+                push(areas, area(shape));
+            }
+            let nested = match Option::Some(Shape::Rect(1, 2)) {
+                Option::Some(Shape::Rect(w, _)) => w,
+                Option::Some(_) => -1,
+                Option::None => -2,
+            };
+            let flags = match [true, false][1] {
+                true => \"yes\",
+                false => \"no\",
+            };
+            return [areas, nested, flags, describe(0), describe(-1), describe(7),
+                Shape::Rect(1, \"a\"), Shape::Empty == Shape::Empty];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[[12, 12, 0], 1, \"no\", \"zero\", \"minus one\", \"other\", \
+             Shape::Rect(1, \"a\"), true]"
+        );
+    }
+
+    #[test]
+    fn literal_patterns_match_equal_values() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn kind(n) {
+            return match n {
+                1 => \"one\",
+                2.5 => \"two and a half\",
+                \"1\" => \"string\",
+                _ => \"other\",
+            };
+        }
+
+        fn main() {
+            let three = match 3 {
+                3.0 => true,
+                _ => false,
+            };
+            return [kind(1.0), kind(1), kind(2.5), kind(\"1\"), kind(true), three];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[\"one\", \"one\", \"two and a half\", \"string\", \"other\", true]"
+        );
+    }
+
     #[test]
     fn while_loop() {
-        let salt = Salt::new();
-        let value = salt.run(
-            "
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
         fn main() {
-            i = 1;
-            product = 1;
+            let i = 1;
+            let product = 1;
             while i <= 10 {
                 product = product * i;
                 i = i + 1;
@@ -69,15 +728,90 @@ mod interpreter_tests {
             return product;
         }
         ",
-        );
+            )
+            .unwrap();
         assert_eq!(value, Value::Integer(3628800));
     }
 
+    #[test]
+    fn break_and_continue() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let i = 0;
+            let sum = 0;
+            while true {
+                i = i + 1;
+                if i % 2 == 0 {
+                    continue;
+                }
+                if i > 9 {
+                    break;
+                }
+                sum = sum + i;
+            }
+            return sum;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(25));
+    }
+
+    #[test]
+    fn for_loop() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let a = 0;
+            for i in 0..5 {
+                a = a * 10 + i;
+            }
+            let b = 0;
+            for i in 1..=9 step 3 {
+                b = b * 10 + i;
+            }
+            let c = 0;
+            for i in 5..0 step -2 {
+                if i == 1 {
+                    break;
+                }
+                c = c * 10 + i;
+            }
+            return a + b + c;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(1234 + 147 + 53));
+    }
+
+    #[test]
+    fn for_loop_variable_is_scoped_to_body() {
+        let salt = BothBackends::new();
+        let error = salt
+            .run(
+                "
+        fn main() {
+            for i in 0..3 {}
+            return i;
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "No such variable: i");
+    }
+
     #[test]
     fn functions() {
-        let salt = Salt::new();
-        let value = salt.run(
-            "
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
         fn main() {
             return a(1, 2, 3) + b(4, 5) + c(6) + d();
         }
@@ -98,28 +832,226 @@ mod interpreter_tests {
             return 1;
         }
         ",
-        );
+            )
+            .unwrap();
 
         assert_eq!(value, Value::Integer(15));
     }
 
+    #[test]
+    fn globals_and_constants() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        const BASE = 2;
+        const LIMIT = double(BASE);
+        global calls = 0;
+
+        fn main() {
+            let results = [];
+            for i in 0..LIMIT {
+                push(results, count(i));
+            }
+            let calls = 100;
+            calls = calls + 1;
+            return [LIMIT, calls, results];
+        }
+
+        fn double(n) {
+            return n * 2;
+        }
+
+        fn count(i) {
+            calls = calls + 1;
+            return calls + i;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[4, 101, [1, 3, 5, 7]]");
+
+        // Initializers only see the globals declared before them.
+        let error = salt
+            .run("const A = B;\nconst B = 1;\nfn main() {}")
+            .unwrap_err();
+        assert_eq!(error.message, "No such variable: B");
+    }
+
+    #[test]
+    fn closures() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let factor = 3;
+            let scale = fn(x) { return x * factor; };
+            factor = 100;
+            let odd = filter([1, 2, 3, 4, 5], fn(x) { return x % 2 == 1; });
+            return [map(odd, scale), map([[1], [2, 3]], len), adder(1)(2)];
+        }
+
+        fn map(array, f) {
+            let result = [];
+            for element in array {
+                push(result, f(element));
+            }
+            return result;
+        }
+
+        fn filter(array, keep) {
+            let result = [];
+            for element in array {
+                if keep(element) {
+                    push(result, element);
+                }
+            }
+            return result;
+        }
+
+        fn adder(n) {
+            return fn(x) { return x + n; };
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[[3, 9, 15], [1, 2], 3]");
+    }
+
+    #[test]
+    fn tail_calls() {
+        // Deep enough to overflow the stack if every call used a new frame.
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let countdown = fn(f, n) {
+                if n == 0 {
+                    return \"done\";
+                }
+                return f(f, n - 1);
+            };
+            return [sum(100000, 0), is_even(100001), countdown(countdown, 100000), drain(100000)];
+        }
+
+        fn sum(n, total) {
+            if n == 0 {
+                return total;
+            }
+            return sum(n - 1, total + n);
+        }
+
+        fn is_even(n) {
+            if n == 0 {
+                return true;
+            }
+            return is_odd(n - 1);
+        }
+
+        fn is_odd(n) {
+            if n == 0 {
+                return false;
+            }
+            return is_even(n - 1);
+        }
+
+        fn drain(n) {
+            for i in [n] {
+                if i == 0 {
+                    return len([i]);
+                }
+                return drain(i - 1);
+            }
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[5000050000, false, \"done\", 1]");
+
+        // Calls in the arms of a returned match are in tail position too.
+        let value = salt
+            .run(
+                "
+        fn main() {
+            return [f(200000), g(200000)];
+        }
+
+        fn f(n) {
+            return match n {
+                0 => 0,
+                _ => f(n - 1),
+            };
+        }
+
+        fn g(n) {
+            return match n % 2 {
+                0 => match n {
+                    0 => len([]),
+                    _ => g(n - 1),
+                },
+                _ => g(n - 1),
+            };
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[0, 0]");
+
+        let error = salt
+            .run("fn main() { return f(1, 2); }\nfn f(x) { return x; }")
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Function `f` expects 1 argument but was given 2"
+        );
+
+        let value = salt
+            .run("fn main() { let f = main; return [f, len]; }")
+            .unwrap();
+        assert_eq!(value.to_string(), "[<fn main>, <fn len>]");
+
+        // Each iteration's variables are captured, through nested lambdas.
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let makers = [];
+            for i in 0..3 {
+                let j = i * 10;
+                push(makers, fn() { return fn() { return i + j; }; });
+            }
+            let results = [];
+            for make in makers {
+                push(results, make()());
+            }
+            return results;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[0, 11, 22]");
+    }
+
     #[test]
     fn fib() {
-        let salt = Salt::new();
-        let value = salt.run(
-            "
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
         fn main() {
-            result_iter = fib_iter(10);
+            let result_iter = fib_iter(10);
             print(result_iter);
             return fib_iter(10) == fib_rec(10);
         }
 
         fn fib_iter(i) {
-            a = 0;
-            b = 1;
-            j = 0;
+            let a = 0;
+            let b = 1;
+            let j = 0;
             while j < i {
-                t = b;
+                let t = b;
                 b = a + b;
                 a = t;
                 j = j + 1;
@@ -137,7 +1069,860 @@ mod interpreter_tests {
             return fib_rec(i - 1) + fib_rec(i - 2);
         }
         ",
-        );
+            )
+            .unwrap();
         assert_eq!(value, Value::Boolean(true));
     }
+    #[test]
+    fn block_scoping() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let x = 1;
+            let total = 0;
+            if true {
+                let x = 10;
+                total = total + x;
+                let inner = 5;
+            }
+            total = total + x;
+            return total;
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(11));
+    }
+
+    #[test]
+    fn block_local_variables_are_dropped() {
+        let salt = BothBackends::new();
+        let error = salt
+            .run(
+                "
+        fn main() {
+            if true {
+                let inner = 5;
+            }
+            return inner;
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "No such variable: inner");
+    }
+
+    #[test]
+    fn assignment_requires_declaration() {
+        let salt = BothBackends::new();
+        let error = salt
+            .run(
+                "
+        fn main() {
+            x = 1;
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "Cannot assign to undeclared variable `x`");
+    }
+
+    #[test]
+    fn captured_variables_are_read_only() {
+        let salt = BothBackends::new();
+        let error = salt
+            .run(
+                "
+        fn main() {
+            let c = 0;
+            let inc = fn() {
+                c = c + 1;
+                return c;
+            };
+            return inc();
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(error.kind, crate::error::ErrorKind::Check);
+        assert_eq!(error.message, "Cannot assign to captured variable `c`");
+        let span = error.span.unwrap();
+        assert_eq!((span.line, span.column), (5, 17));
+
+        // The lambda's own variables, including shadowing ones, are assignable.
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let c = 0;
+            let inc = fn(c) {
+                c = c + 1;
+                let d = c;
+                d = d * 10;
+                return d;
+            };
+            return [inc(1), c];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[20, 0]");
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::BothBackends;
+    use crate::{error::ErrorKind, salt::Salt, span::Span};
+
+    #[test]
+    fn lex_error() {
+        let salt = BothBackends::new();
+        let error = salt.run("fn main() { return 1 @ 2; }").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Lex);
+        assert_eq!(error.span, Some(Span::new(21, 1, 22, 1)));
+    }
+
+    #[test]
+    fn parse_error() {
+        let salt = BothBackends::new();
+        let error = salt.run("fn main() {\n    a(1 2);\n}").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Parse);
+        assert_eq!(error.span, Some(Span::new(20, 2, 9, 1)));
+    }
+
+    #[test]
+    fn every_syntax_error() {
+        let source = "fn main() {\n    a(1 2);\n    b = ;\n}\n\nfn ok() {\n    return 1;\n}";
+        let errors = Salt::new().run(source).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.unwrap().line).collect();
+        assert_eq!(lines, vec![2, 3]);
+
+        // The program holds what parsed.
+        let (program, errors) = Salt::new().parse(source);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(program.globals.len(), 2);
+    }
+
+    #[test]
+    fn runtime_error() {
+        let salt = BothBackends::new();
+        let error = salt
+            .run(
+                "
+        fn main() {
+            return 1 + true;
+        }
+        ",
+            )
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Runtime);
+        assert_eq!(error.span.unwrap().line, 3);
+        assert_eq!(
+            error.message,
+            "Type error: cannot apply Addition to Integer and Boolean"
+        );
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        let salt = BothBackends::new();
+        let cases = [
+            ("return 1 / (2 - 2);", "Division by zero", 12),
+            ("return 7 % 0;", "Modulo by zero", 12),
+            ("return 100000000000000000000 / 0;", "Division by zero", 12),
+        ];
+        for (body, message, column) in cases {
+            let source = format!("fn main() {{\n    {body}\n}}");
+            let error = salt.run(&source).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Runtime);
+            assert_eq!(error.message, message);
+            let span = error.span.unwrap();
+            assert_eq!((span.line, span.column), (2, column));
+        }
+    }
+
+    #[test]
+    fn call_errors() {
+        let salt = BothBackends::new();
+        let cases = [
+            (
+                "let x = 1; return x(2);",
+                "Type error: cannot call Integer",
+                23,
+            ),
+            (
+                "return fn(x) { return x; }(1, 2);",
+                "Function `<lambda>` expects 1 argument but was given 2",
+                12,
+            ),
+        ];
+        for (body, message, column) in cases {
+            let source = format!("fn main() {{\n    {body}\n}}");
+            let error = salt.run(&source).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Runtime);
+            assert_eq!(error.message, message);
+            let span = error.span.unwrap();
+            assert_eq!((span.line, span.column), (2, column));
+        }
+    }
+
+    #[test]
+    fn collection_errors() {
+        let salt = BothBackends::new();
+        let cases = [
+            (
+                "let a = [1, 2]; return a[2];",
+                "Index 2 is out of bounds for an Array of length 2",
+                28,
+            ),
+            (
+                "let a = [1, 2]; a[-1] = 0;",
+                "Index -1 is out of bounds for an Array of length 2",
+                21,
+            ),
+            (
+                "return [][true];",
+                "Type error: Array index must be an Integer, found Boolean",
+                15,
+            ),
+            ("return 3[0];", "Type error: cannot index into Integer", 12),
+            ("return pop([]);", "Cannot pop from an empty Array", 12),
+            (
+                "return { \"a\": 1 }[\"b\"];",
+                "Key \"b\" not found in Map",
+                12,
+            ),
+            (
+                "let m = {}; m[1.5] = 0;",
+                "Type error: Float cannot be used as a Map key",
+                19,
+            ),
+            (
+                "return { []: 1 };",
+                "Type error: Array cannot be used as a Map key",
+                14,
+            ),
+            (
+                "for x in 5 {}",
+                "Type error: cannot iterate over Integer",
+                14,
+            ),
+            (
+                "return Pair { a: 1 };",
+                "Missing field `b` in `Pair` literal",
+                12,
+            ),
+            (
+                "return Pair { a: 1, a: 2 };",
+                "Field `a` is specified more than once",
+                25,
+            ),
+            (
+                "return Pair { c: 1 };",
+                "Struct `Pair` has no field `c`",
+                19,
+            ),
+            (
+                "let p = Pair { a: 1, b: 2 }; p.c = 0;",
+                "Struct `Pair` has no field `c`",
+                36,
+            ),
+            (
+                "return 1.a;",
+                "Type error: cannot access field `a` on Integer",
+                12,
+            ),
+            ("return Nope {};", "No such struct: Nope", 12),
+            (
+                "return len(1, 2);",
+                "Function `len` expects 1 argument but was given 2",
+                12,
+            ),
+        ];
+        for (body, message, column) in cases {
+            let source = format!("fn main() {{\n    {body}\n}}\nstruct Pair {{ a, b }}");
+            let error = salt.run(&source).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Runtime);
+            assert_eq!(error.message, message);
+            let span = error.span.unwrap();
+            assert_eq!((span.line, span.column), (2, column));
+        }
+    }
+
+    #[test]
+    fn match_checks() {
+        let salt = BothBackends::new();
+        let prelude = "enum Shape { Circle(r), Rect(w, h), Empty }\n\
+                       enum Option { Some(value), None }\n";
+        let cases = [
+            (
+                "match s { Shape::Circle(_) => {} Shape::Empty => {} }",
+                "Non-exhaustive match: `Shape::Rect(_, _)` is not covered",
+            ),
+            (
+                "return match o { Option::Some(Shape::Circle(_)) => 1, Option::None => 2 };",
+                "Non-exhaustive match: `Option::Some(Shape::Rect(_, _))` is not covered",
+            ),
+            (
+                "return match b { true => 1 };",
+                "Non-exhaustive match: `false` is not covered",
+            ),
+            (
+                "return match n { 1 => 1, 2 => 2 };",
+                "Non-exhaustive match: `_` is not covered",
+            ),
+            (
+                "return Shape::Circle(1, 2);",
+                "Variant `Shape::Circle` has 1 field but was given 2",
+            ),
+            (
+                "match s { Shape::Square => {} }",
+                "Enum `Shape` has no variant `Square`",
+            ),
+            ("return Color::Red;", "No such enum: Color"),
+        ];
+        for (body, message) in cases {
+            let source = format!("{prelude}fn main(s, o, b, n) {{\n    {body}\n}}");
+            let errors = salt.parse(&source).unwrap_err();
+            assert_eq!(errors.len(), 1, "{body}");
+            assert_eq!(errors[0].kind, ErrorKind::Check);
+            assert_eq!(errors[0].message, message);
+            assert_eq!(errors[0].span.unwrap().line, 4);
+        }
+        let exhaustive = "return match [o, b] { _ => 0 } + match o { \
+                          Option::Some(Shape::Circle(_)) => 1, Option::Some(Shape::Rect(_, _)) => 2, \
+                          Option::Some(Shape::Empty) => 3, Option::None => 4 };";
+        assert!(salt
+            .parse(&format!("{prelude}fn main(o, b) {{ {exhaustive} }}"))
+            .is_ok());
+    }
+
+    #[test]
+    fn undefined_names() {
+        let salt = BothBackends::new();
+        let cases = [
+            ("return nothing(1);", "No such function: nothing", 12),
+            ("return x + 1;", "No such variable: x", 12),
+            (
+                "if true { let y = 1; } return y;",
+                "No such variable: y",
+                35,
+            ),
+            (
+                "let f = fn() { return g; }; let g = 1;",
+                "No such variable: g",
+                27,
+            ),
+            ("z = 1;", "Cannot assign to undeclared variable `z`", 5),
+            (
+                "main = 1;",
+                "Cannot assign to undeclared variable `main`",
+                5,
+            ),
+        ];
+        for (body, message, column) in cases {
+            let source = format!("fn main() {{\n    {body}\n}}");
+            let errors = salt.parse(&source).unwrap_err();
+            assert_eq!(errors.len(), 1, "{body}");
+            assert_eq!(errors[0].kind, ErrorKind::Check);
+            assert_eq!(errors[0].message, message);
+            let span = errors[0].span.unwrap();
+            assert_eq!((span.line, span.column), (2, column));
+        }
+        // Errors are reported before anything runs.
+        let error = salt
+            .run("fn main() { print(1); return missing; }")
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Check);
+    }
+
+    #[test]
+    fn missing_main() {
+        let salt = BothBackends::new();
+        let error = salt.run("fn start() {}").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Runtime);
+        assert_eq!(error.span, None);
+    }
+}
+
+#[cfg(test)]
+mod diagnostic_tests {
+    use super::BothBackends;
+    use crate::{diagnostic, source::SourceFile};
+
+    #[test]
+    fn arity_note() {
+        let source = "fn main() {\n    return f(1, 2);\n}\n\nfn f(x) {\n    return x;\n}\n";
+        let error = BothBackends::new().run(source).unwrap_err();
+        let rendered = diagnostic::render(&error, &[SourceFile::new("test.salt", source)]);
+        let expected = "\
+runtime error: Function `f` expects 1 argument but was given 2
+ --> test.salt:2:12
+  |
+2 |     return f(1, 2);
+  |            ^^^^^^^
+  |
+note: function `f` declared with 1 parameter here
+ --> test.salt:5:1
+  |
+5 | fn f(x) {
+  | ^^^^^^^
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn constant_assignment() {
+        // Reported before running, even where the assignment never runs.
+        let source = "const LIMIT = 10;\n\nfn main() {\n    if false { LIMIT = 11; }\n}\n";
+        let error = BothBackends::new().run(source).unwrap_err();
+        let rendered = diagnostic::render(&error, &[SourceFile::new("test.salt", source)]);
+        let expected = "\
+error: Cannot assign to constant `LIMIT`
+ --> test.salt:4:16
+  |
+4 |     if false { LIMIT = 11; }
+  |                ^^^^^^^^^^^
+  |
+note: constant `LIMIT` defined here
+ --> test.salt:1:1
+  |
+1 | const LIMIT = 10;
+  | ^^^^^^^^^^^^^^^^^
+";
+        assert_eq!(rendered, expected);
+
+        // A local variable of the same name shadows the constant.
+        let source = "const LIMIT = 10;\nfn main() { let LIMIT = 1; LIMIT = 2; return LIMIT; }";
+        assert_eq!(BothBackends::new().run(source).unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn unexpected_end_of_file() {
+        let source = "fn main() {\n    return 1;\n";
+        let error = BothBackends::new().run(source).unwrap_err();
+        let rendered = diagnostic::render(&error, &[SourceFile::new("test.salt", source)]);
+        assert!(rendered.starts_with("parse error: Unexpected end of file (expected `}`)\n"));
+        assert!(rendered.ends_with("3 |\n  | ^\n"));
+    }
+}
+
+#[cfg(test)]
+mod optimizer_tests {
+    use super::BothBackends;
+    use crate::{
+        ast::{BinaryOpType, Expr, Global, Statement},
+        optimizer,
+        value::Value,
+    };
+
+    /// The statements of `main` in `source` after optimization.
+    fn optimized_main(source: &str) -> Vec<Statement> {
+        let mut program = BothBackends::new().parse(source).unwrap();
+        optimizer::optimize(&mut program);
+        program
+            .globals
+            .into_iter()
+            .find_map(|global| match global {
+                Global::Function(function) if function.name == "main" => {
+                    Some(function.block.statements)
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn constant_folding() {
+        let statements =
+            optimized_main("fn main() { return 2 * (3 + 4) - -1 == 15 && !false || 1 / 0; }");
+        let [Statement::Return(return_stmt)] = statements.as_slice() else {
+            panic!("{statements:?}");
+        };
+        assert!(
+            matches!(&return_stmt.expr, Expr::Literal(literal) if literal.value == Value::Boolean(true))
+        );
+
+        // Errors are left for the program to report when it runs.
+        let statements = optimized_main("fn main() { return 1 / 0; }");
+        let [Statement::Return(return_stmt)] = statements.as_slice() else {
+            panic!("{statements:?}");
+        };
+        assert!(matches!(&return_stmt.expr, Expr::BinaryOp(_)));
+    }
+
+    #[test]
+    fn dead_code() {
+        let statements = optimized_main(
+            "
+        fn main(x) {
+            if false {
+                print(1);
+            }
+            if 1 > 2 {
+                print(2);
+            } else if true {
+                print(3);
+            } else {
+                print(4);
+            }
+            while false {
+                print(5);
+            }
+            return (x - 1) * 1;
+            print(6);
+        }
+        ",
+        );
+        let [Statement::Print(print), Statement::Return(return_stmt)] = statements.as_slice()
+        else {
+            panic!("{statements:?}");
+        };
+        assert!(
+            matches!(&print.expr, Expr::Literal(literal) if literal.value == Value::Integer(3))
+        );
+        assert!(matches!(
+            &return_stmt.expr,
+            Expr::BinaryOp(op) if op.op_type == BinaryOpType::Subtraction
+        ));
+    }
+
+    #[test]
+    fn preserves_behavior() {
+        let salt = BothBackends::new();
+        let value = salt
+            .run(
+                "
+        fn main() {
+            let s = \"a\";
+            if true {
+                let s = 1;
+            }
+            return [s + 0, -0.0 + 0, (2 - 3) * 1, 0 + 4 * 2, false && 1];
+        }
+        ",
+            )
+            .unwrap();
+        assert_eq!(value.to_string(), "[\"a0\", 0.0, -1, 8, false]");
+
+        let error = salt
+            .run("fn main() {\n    let s = \"a\";\n    return s * 1;\n}")
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Type error: cannot apply Multiplication to String and Integer"
+        );
+    }
+}
+
+#[cfg(test)]
+mod module_tests {
+    use std::path::PathBuf;
+    use std::{env, fs, process};
+
+    use super::BothBackends;
+    use crate::{diagnostic, error::ErrorKind, module::Loader};
+
+    /// Writes `files` into a fresh directory named after `test`.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("salt-{test}-{}", process::id()));
+        for (name, text) in files {
+            let path = directory.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn imports() {
+        let directory = write_files(
+            "imports",
+            &[
+                (
+                    "main.salt",
+                    "
+        import \"lib/util.salt\";
+        import \"lib/math.salt\";
+
+        fn main() {
+            util.bump();
+            util.bump();
+            let gcd = math.gcd;
+            return [gcd(12, 18), math.count, rem(), math.LIMIT];
+        }
+
+        fn rem() {
+            return \"main\";
+        }
+        ",
+                ),
+                (
+                    "lib/util.salt",
+                    "
+        import math;
+
+        fn bump() {
+            math.count = math.count + 1;
+        }
+        ",
+                ),
+                (
+                    "lib/math.salt",
+                    "
+        const LIMIT = 10;
+        global count = 0;
+
+        fn gcd(a, b) {
+            if b == 0 {
+                return a;
+            }
+            return gcd(b, rem(a, b));
+        }
+
+        fn rem(a, b) {
+            return a % b;
+        }
+        ",
+                ),
+            ],
+        );
+        let value = BothBackends::new()
+            .run_file(&directory.join("main.salt"))
+            .unwrap();
+        // `util` and `main` share the one instance of `math`.
+        assert_eq!(value.to_string(), "[6, 2, \"main\", 10]");
+    }
+
+    #[test]
+    fn import_errors() {
+        let directory = write_files(
+            "import_errors",
+            &[
+                ("a.salt", "import b;\nfn main() {}"),
+                ("b.salt", "import a;"),
+                ("missing.salt", "import nothing;\nfn main() {}"),
+                ("twice.salt", "import c;\nimport \"c.salt\";\nfn main() {}"),
+                ("member.salt", "import c;\nfn main() { return c.nothing; }"),
+                ("c.salt", "const LIMIT = 1;"),
+            ],
+        );
+        let salt = BothBackends::new();
+        let error = salt.run_file(&directory.join("a.salt")).unwrap_err();
+        let a = directory.join("a.salt");
+        let b = directory.join("b.salt");
+        assert_eq!(
+            error.message,
+            format!(
+                "Import cycle: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display()
+            )
+        );
+        assert_eq!(error.span.unwrap().file, 1);
+
+        let error = salt.run_file(&directory.join("missing.salt")).unwrap_err();
+        let expected = format!(
+            "Cannot read `{}`: ",
+            directory.join("nothing.salt").display()
+        );
+        assert!(error.message.starts_with(&expected));
+
+        let error = salt.run_file(&directory.join("twice.salt")).unwrap_err();
+        assert_eq!(error.message, "Module `c` is imported more than once");
+
+        let error = salt.run_file(&directory.join("member.salt")).unwrap_err();
+        assert_eq!(error.message, "Module `c` has no member `nothing`");
+    }
+
+    #[test]
+    fn types_belong_to_their_module() {
+        let main = "
+        import shapes;
+
+        enum Shape { Circle(r), Square(side) }
+        struct Point { x, y }
+
+        fn main() {
+            let theirs = shapes.circle(1);
+            let mine = Shape::Circle(1);
+            let kind = match theirs {
+                Shape::Circle(_) => \"mine\",
+                shapes.Shape::Circle(_) => \"theirs\",
+                _ => \"other\",
+            };
+            let size = match theirs {
+                shapes.Shape::Circle(r) => r,
+                shapes.Shape::Square(side) => side,
+            };
+            let origin = Point { x: 0, y: 0 };
+            return [
+                theirs,
+                theirs == mine,
+                theirs == shapes.Shape::Circle(1),
+                shapes.origin() == origin,
+                shapes.origin() == shapes.Point { x: 0, y: 0 },
+                kind,
+                size,
+                shapes.is_circle(theirs),
+                shapes.is_circle(mine),
+            ];
+        }
+        ";
+        let shapes = "
+        enum Shape { Circle(r), Square(side) }
+        struct Point { x, y }
+
+        fn circle(r) {
+            return Shape::Circle(r);
+        }
+
+        fn origin() {
+            return Point { x: 0, y: 0 };
+        }
+
+        fn is_circle(shape) {
+            return match shape {
+                Shape::Circle(_) => true,
+                _ => false,
+            };
+        }
+        ";
+        let body = |body: &str| {
+            format!("import shapes;\nenum Shape {{ Circle(r), Square(side) }}\nfn main() {{\n    {body}\n}}\n")
+        };
+        let directory = write_files(
+            "types_belong_to_their_module",
+            &[
+                ("main.salt", main),
+                ("shapes.salt", shapes),
+                (
+                    "unqualified.salt",
+                    &body("return match shapes.circle(1) { Shape::Circle(r) => r, Shape::Square(x) => x };"),
+                ),
+                (
+                    "exhaustive.salt",
+                    &body("return match shapes.circle(1) { shapes.Shape::Circle(r) => r };"),
+                ),
+                ("variant.salt", &body("return shapes.Shape::Triangle;")),
+                ("module.salt", &body("return nothing.Point { x: 0, y: 0 };")),
+                ("struct.salt", &body("return shapes.Nothing { x: 0 };")),
+            ],
+        );
+        let salt = BothBackends::new();
+        let value = salt.run_file(&directory.join("main.salt")).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[Shape::Circle(1), false, true, false, true, \"theirs\", 1, true, false]"
+        );
+
+        // Unqualified patterns only match the module's own enum, however
+        // the checker sees them.
+        let error = salt
+            .run_file(&directory.join("unqualified.salt"))
+            .unwrap_err();
+        assert_eq!(error.message, "No match arm matches Shape::Circle(1)");
+        assert!(error.notes[0]
+            .message
+            .starts_with("this `Shape` is an enum of another module"));
+
+        let error = salt
+            .run_file(&directory.join("exhaustive.salt"))
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Check);
+        assert_eq!(
+            error.message,
+            "Non-exhaustive match: `shapes.Shape::Square(_)` is not covered"
+        );
+
+        let error = salt.run_file(&directory.join("variant.salt")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Check);
+        assert_eq!(
+            error.message,
+            "Enum `shapes.Shape` has no variant `Triangle`"
+        );
+        assert_eq!(error.notes[0].span.unwrap().file, 1);
+
+        let error = salt.run_file(&directory.join("module.salt")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Check);
+        assert_eq!(error.message, "No such module: nothing");
+
+        let error = salt.run_file(&directory.join("struct.salt")).unwrap_err();
+        assert_eq!(error.message, "No such struct: shapes.Nothing");
+    }
+
+    #[test]
+    fn errors_point_into_imported_files() {
+        let directory = write_files(
+            "errors_point_into_imported_files",
+            &[
+                (
+                    "main.salt",
+                    "import config;\n\nfn main() {\n    config.LIMIT = 2;\n}\n",
+                ),
+                ("config.salt", "const LIMIT = 1;\n"),
+            ],
+        );
+        let mut loader = Loader::new();
+        let errors = loader
+            .load_file(&directory.join("main.salt"))
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        let rendered = diagnostic::render(&errors[0], loader.files());
+        let expected = format!(
+            "\
+error: Cannot assign to constant `LIMIT`
+ --> {}:4:5
+  |
+4 |     config.LIMIT = 2;
+  |     ^^^^^^^^^^^^^^^^^
+  |
+note: constant `LIMIT` defined here
+ --> {}:1:1
+  |
+1 | const LIMIT = 1;
+  | ^^^^^^^^^^^^^^^^
+",
+            directory.join("main.salt").display(),
+            directory.join("config.salt").display()
+        );
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn unknown_fields_point_at_the_struct_of_their_module() {
+        let directory = write_files(
+            "unknown_fields_point_at_the_struct_of_their_module",
+            &[
+                (
+                    "get.salt",
+                    "import shapes;\nstruct Point { x }\nfn main() {\n    return shapes.origin().z;\n}\n",
+                ),
+                (
+                    "set.salt",
+                    "import shapes;\nstruct Point { x }\nfn main() {\n    shapes.origin().z = 1;\n}\n",
+                ),
+                (
+                    "shapes.salt",
+                    "struct Point { x, y }\nfn origin() {\n    return Point { x: 0, y: 0 };\n}\n",
+                ),
+            ],
+        );
+        let salt = BothBackends::new();
+        for file in ["get.salt", "set.salt"] {
+            let error = salt.run_file(&directory.join(file)).unwrap_err();
+            assert_eq!(error.message, "Struct `Point` has no field `z`");
+            assert_eq!(error.notes[0].message, "struct `Point` defined here");
+            let definition = error.notes[0].span.unwrap();
+            assert_eq!(
+                (definition.file, definition.line, definition.column),
+                (1, 1, 1)
+            );
+        }
+    }
 }
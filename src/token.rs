@@ -1,12 +1,29 @@
-#[derive(Debug, PartialEq, Eq)]
+use std::fmt;
+
+use crate::bigint::BigInt;
+use crate::span::Span;
+
+#[derive(Debug, PartialEq)]
 pub enum Token {
     End,
 
     // Keywords
     Function,
+    Struct,
+    Enum,
+    Import,
+    Const,
+    Global,
+    Match,
+    Let,
     If,
+    Else,
     While,
+    For,
+    In,
     Return,
+    Break,
+    Continue,
     Print,
     Time,
     True,
@@ -17,8 +34,16 @@ pub enum Token {
     RParen,
     LCurly,
     RCurly,
+    LSquare,
+    RSquare,
     Semicolon,
     Comma,
+    Colon,
+    ColonColon,
+    Dot,
+    FatArrow,
+    DotDot,
+    DotDotEqual,
 
     // Opetator
     Equal,
@@ -28,6 +53,8 @@ pub enum Token {
     Slash,
     Percent,
     Bang,
+    DoubleAmpersand,
+    DoublePipe,
 
     DoubleEqual,
     NotEqual,
@@ -39,4 +66,77 @@ pub enum Token {
     // Name + Literals
     Name(String),
     Integer(i64),
+    /// An integer literal too large for `i64`.
+    BigInteger(BigInt),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::End => return write!(f, "end of file"),
+            Token::Function => "fn",
+            Token::Struct => "struct",
+            Token::Enum => "enum",
+            Token::Import => "import",
+            Token::Const => "const",
+            Token::Global => "global",
+            Token::Match => "match",
+            Token::Let => "let",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::For => "for",
+            Token::In => "in",
+            Token::Return => "return",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::Print => "print",
+            Token::Time => "time",
+            Token::True => "true",
+            Token::False => "false",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LCurly => "{",
+            Token::RCurly => "}",
+            Token::LSquare => "[",
+            Token::RSquare => "]",
+            Token::Semicolon => ";",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::ColonColon => "::",
+            Token::Dot => ".",
+            Token::FatArrow => "=>",
+            Token::DotDot => "..",
+            Token::DotDotEqual => "..=",
+            Token::Equal => "=",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Bang => "!",
+            Token::DoubleAmpersand => "&&",
+            Token::DoublePipe => "||",
+            Token::DoubleEqual => "==",
+            Token::NotEqual => "!=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Name(name) => return write!(f, "name `{name}`"),
+            Token::Integer(integer) => return write!(f, "integer `{integer}`"),
+            Token::BigInteger(integer) => return write!(f, "integer `{integer}`"),
+            Token::Float(float) => return write!(f, "float `{float:?}`"),
+            Token::String(string) => return write!(f, "string {string:?}"),
+        };
+        write!(f, "`{text}`")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
@@ -1,6 +1,494 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Function;
+use crate::bigint::BigInt;
+use crate::error::SaltError;
+use crate::span::Span;
+
+/// Values compare equal when they have the same type and contents; values of
+/// different types are never equal. Salt's `==` applies `Value::promote` to
+/// its operands first, so `1 == 1.0` holds there but not here.
+#[derive(Debug, Clone)]
 pub enum Value {
     Unit,
     Boolean(bool),
     Integer(i64),
+    /// An Integer outside the range of `i64`. Results are normalized with
+    /// `Value::from_bigint`, so a `BigInt` never holds a value that would fit
+    /// in an `Integer`.
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    String(Rc<str>),
+    /// Arrays are shared by reference: copies of the value alias the same
+    /// elements, so a function can modify an array it is passed.
+    Array(Rc<RefCell<Vec<Value>>>),
+    /// Maps are shared by reference, like arrays.
+    Map(Rc<RefCell<Map>>),
+    /// Struct instances are shared by reference, like arrays.
+    Struct(Rc<Instance>),
+    Enum(Rc<EnumValue>),
+    Function(Rc<Closure>),
+    /// A function provided by the interpreter, such as `len`.
+    Builtin(&'static str),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Unit => "Unit",
+            Value::Boolean(_) => "Boolean",
+            Value::Integer(_) | Value::BigInt(_) => "Integer",
+            Value::Float(_) => "Float",
+            Value::String(_) => "String",
+            Value::Array(_) => "Array",
+            Value::Map(_) => "Map",
+            Value::Struct(instance) => &instance.name,
+            Value::Enum(value) => &value.enum_name,
+            Value::Function(_) | Value::Builtin(_) => "Function",
+        }
+    }
+
+    pub fn array(elements: Vec<Value>) -> Value {
+        Value::Array(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(map: Map) -> Value {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    /// Wraps an arbitrary-precision result, demoting it to an `Integer` when
+    /// it fits.
+    pub fn from_bigint(value: BigInt) -> Value {
+        match value.to_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::BigInt(Rc::new(value)),
+        }
+    }
+
+    /// Converts either integer representation to a `BigInt`.
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::Integer(i) => Some(BigInt::from_i64(*i)),
+            Value::BigInt(i) => Some(BigInt::clone(i)),
+            _ => None,
+        }
+    }
+
+    /// Numeric promotion for binary operators: when one operand is a Float
+    /// and the other an Integer, the Integer is converted to a Float.
+    pub fn promote(left: Value, right: Value) -> (Value, Value) {
+        match (left, right) {
+            (Value::Integer(l), Value::Float(r)) => (Value::Float(l as f64), Value::Float(r)),
+            (Value::Float(l), Value::Integer(r)) => (Value::Float(l), Value::Float(r as f64)),
+            (Value::BigInt(l), Value::Float(r)) => (Value::Float(l.to_f64()), Value::Float(r)),
+            (Value::Float(l), Value::BigInt(r)) => (Value::Float(l), Value::Float(r.to_f64())),
+            operands => operands,
+        }
+    }
+
+    /// Orders two values of the same type. Returns `None` for values of
+    /// different types, which have no meaningful order.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Unit, Value::Unit) => Some(Ordering::Equal),
+            (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+            (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+            (Value::Integer(_) | Value::BigInt(_), Value::Integer(_) | Value::BigInt(_)) => {
+                Some(self.to_bigint()?.cmp(&other.to_bigint()?))
+            }
+            (Value::Float(l), Value::Float(r)) => l.partial_cmp(r),
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, &mut Vec::new())
+    }
+}
+
+/// The address of the shared contents of an array, map or struct, which
+/// identifies it while walking values that may contain themselves.
+fn address(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Array(elements) => Some(Rc::as_ptr(elements) as *const ()),
+        Value::Map(map) => Some(Rc::as_ptr(map) as *const ()),
+        Value::Struct(instance) => Some(Rc::as_ptr(instance) as *const ()),
+        _ => None,
+    }
+}
+
+/// Writes `value`. `open` holds the addresses of the collections being
+/// written further out; a collection that contains itself is written as
+/// `[...]`, `{...}` or `Name {...}` where it recurs.
+fn write_value(
+    f: &mut fmt::Formatter<'_>,
+    value: &Value,
+    open: &mut Vec<*const ()>,
+) -> fmt::Result {
+    let address = address(value);
+    if let Some(address) = address {
+        if open.contains(&address) {
+            return match value {
+                Value::Array(_) => write!(f, "[...]"),
+                Value::Map(_) => write!(f, "{{...}}"),
+                Value::Struct(instance) => write!(f, "{} {{...}}", instance.name),
+                _ => unreachable!("only collections have an address"),
+            };
+        }
+        open.push(address);
+    }
+    let result = write_contents(f, value, open);
+    if address.is_some() {
+        open.pop();
+    }
+    result
+}
+
+fn write_contents(
+    f: &mut fmt::Formatter<'_>,
+    value: &Value,
+    open: &mut Vec<*const ()>,
+) -> fmt::Result {
+    match value {
+        Value::Unit => write!(f, "()"),
+        Value::Boolean(v) => write!(f, "{v}"),
+        Value::Integer(v) => write!(f, "{v}"),
+        Value::BigInt(v) => write!(f, "{v}"),
+        // Debug formatting keeps the `.0` of whole numbers, so the output
+        // reads back as a Float.
+        Value::Float(v) => write!(f, "{v:?}"),
+        Value::String(v) => write!(f, "{v}"),
+        Value::Array(elements) => {
+            write!(f, "[")?;
+            for (i, element) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_nested(f, element, open)?;
+            }
+            write!(f, "]")
+        }
+        Value::Map(map) => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{key}")?;
+                write!(f, ": ")?;
+                write_nested(f, value, open)?;
+            }
+            write!(f, "}}")
+        }
+        Value::Struct(instance) => {
+            write!(f, "{} {{", instance.name)?;
+            for (i, (field, value)) in instance.fields.borrow().iter().enumerate() {
+                let separator = if i > 0 { "," } else { "" };
+                write!(f, "{separator} {field}: ")?;
+                write_nested(f, value, open)?;
+            }
+            if instance.fields.borrow().is_empty() {
+                write!(f, "}}")
+            } else {
+                write!(f, " }}")
+            }
+        }
+        Value::Function(closure) => write!(f, "<fn {}>", closure.function.name()),
+        Value::Builtin(name) => write!(f, "<fn {name}>"),
+        Value::Enum(value) => {
+            write!(f, "{}::{}", value.enum_name, value.variant)?;
+            if !value.fields.is_empty() {
+                write!(f, "(")?;
+                for (i, field) in value.fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_nested(f, field, open)?;
+                }
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Writes a value inside a collection. Nested strings are quoted so that
+/// `["a, b"]` and `["a", "b"]` print differently.
+fn write_nested(
+    f: &mut fmt::Formatter<'_>,
+    value: &Value,
+    open: &mut Vec<*const ()>,
+) -> fmt::Result {
+    match value {
+        Value::String(s) => write!(f, "{s:?}"),
+        other => write_value(f, other, open),
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other, &mut Vec::new())
+    }
+}
+
+/// Compares two values. `comparing` holds the pairs of collections being
+/// compared further out, which are taken to be equal where they recur, so
+/// collections that contain themselves compare by their other contents.
+fn equal(left: &Value, right: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    let pair = address(left).zip(address(right));
+    if let Some(pair) = pair {
+        if comparing.contains(&pair) {
+            return true;
+        }
+        comparing.push(pair);
+    }
+    let result = equal_contents(left, right, comparing);
+    if pair.is_some() {
+        comparing.pop();
+    }
+    result
+}
+
+fn equal_contents(
+    left: &Value,
+    right: &Value,
+    comparing: &mut Vec<(*const (), *const ())>,
+) -> bool {
+    let mut all_equal = |left: &[Value], right: &[Value]| {
+        left.len() == right.len()
+            && left
+                .iter()
+                .zip(right)
+                .all(|(left, right)| equal(left, right, comparing))
+    };
+    match (left, right) {
+        (Value::Unit, Value::Unit) => true,
+        (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::Integer(l), Value::Integer(r)) => l == r,
+        (Value::BigInt(l), Value::BigInt(r)) => l == r,
+        (Value::Float(l), Value::Float(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Array(l), Value::Array(r)) => all_equal(&l.borrow(), &r.borrow()),
+        // Maps are equal when they have the same entries, in any order.
+        (Value::Map(l), Value::Map(r)) => {
+            let (l, r) = (l.borrow(), r.borrow());
+            l.len() == r.len()
+                && l.iter().all(|(key, value)| {
+                    r.get(key)
+                        .is_some_and(|other| equal(value, other, comparing))
+                })
+        }
+        (Value::Struct(l), Value::Struct(r)) => {
+            let (l_fields, r_fields) = (l.fields.borrow(), r.fields.borrow());
+            l.name == r.name
+                && l.module == r.module
+                && l_fields.len() == r_fields.len()
+                && l_fields.iter().zip(r_fields.iter()).all(
+                    |((l_name, l_value), (r_name, r_value))| {
+                        l_name == r_name && equal(l_value, r_value, comparing)
+                    },
+                )
+        }
+        (Value::Enum(l), Value::Enum(r)) => {
+            l.enum_name == r.enum_name
+                && l.module == r.module
+                && l.variant == r.variant
+                && all_equal(&l.fields, &r.fields)
+        }
+        // Closures are equal when they are the same function with the same
+        // captured values.
+        (Value::Function(l), Value::Function(r)) => {
+            l.function.same(&r.function) && all_equal(&l.captured, &r.captured)
+        }
+        (Value::Builtin(l), Value::Builtin(r)) => l == r,
+        _ => false,
+    }
+}
+
+/// An instance of a user-defined struct. Its fields are in the order of the
+/// struct definition.
+#[derive(Debug)]
+pub struct Instance {
+    pub name: String,
+    /// The module defining the struct, so that same-named structs of
+    /// different modules are never equal.
+    pub module: usize,
+    fields: RefCell<Vec<(String, Value)>>,
+}
+
+impl Instance {
+    pub fn new(name: String, module: usize, fields: Vec<(String, Value)>) -> Self {
+        Self {
+            name,
+            module,
+            fields: RefCell::new(fields),
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<Value> {
+        self.fields
+            .borrow()
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Updates `field`. Returns `false` if the struct has no such field.
+    pub fn set(&self, field: &str, value: Value) -> bool {
+        match self
+            .fields
+            .borrow_mut()
+            .iter_mut()
+            .find(|(name, _)| name == field)
+        {
+            Some((_, slot)) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A variant of a user-defined enum, together with its fields. Enum values
+/// are immutable.
+#[derive(Debug)]
+pub struct EnumValue {
+    pub enum_name: String,
+    /// The module defining the enum, like `Instance::module`.
+    pub module: usize,
+    pub variant: String,
+    pub fields: Vec<Value>,
+}
+
+/// A function value: a named function or lambda, together with the
+/// variables it captured when it was created.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Callable,
+    /// The values of the function's captures, in order.
+    pub captured: Vec<Value>,
+}
+
+/// The code a closure runs, in the form of the backend that created it.
+#[derive(Debug)]
+pub enum Callable {
+    /// A function of the syntax tree, for the tree-walking interpreter.
+    Tree(Rc<Function>),
+    /// The index of a function in the compiled program, for the VM.
+    Compiled { index: usize, name: Rc<str> },
+}
+
+impl Callable {
+    pub fn name(&self) -> &str {
+        match self {
+            Callable::Tree(function) => &function.name,
+            Callable::Compiled { name, .. } => name,
+        }
+    }
+
+    /// Whether both are the same function.
+    fn same(&self, other: &Callable) -> bool {
+        match (self, other) {
+            (Callable::Tree(l), Callable::Tree(r)) => Rc::ptr_eq(l, r),
+            (Callable::Compiled { index: l, .. }, Callable::Compiled { index: r, .. }) => l == r,
+            _ => false,
+        }
+    }
+}
+
+/// The values that can be used as map keys: those with a structural hash.
+/// Floats and the mutable collections are excluded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Boolean(bool),
+    Integer(i64),
+    BigInt(Rc<BigInt>),
+    String(Rc<str>),
+}
+
+impl Key {
+    /// Converts a value to a key, or reports that its type cannot be used as
+    /// one. `span` locates the key in the source.
+    pub fn from_value(value: &Value, span: Span) -> Result<Key, SaltError> {
+        match value {
+            Value::Boolean(v) => Ok(Key::Boolean(*v)),
+            Value::Integer(v) => Ok(Key::Integer(*v)),
+            Value::BigInt(v) => Ok(Key::BigInt(v.clone())),
+            Value::String(v) => Ok(Key::String(v.clone())),
+            other => Err(SaltError::runtime(
+                format!(
+                    "Type error: {} cannot be used as a Map key",
+                    other.type_name()
+                ),
+                span,
+            )),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Boolean(v) => Value::Boolean(*v),
+            Key::Integer(v) => Value::Integer(*v),
+            Key::BigInt(v) => Value::BigInt(v.clone()),
+            Key::String(v) => Value::String(v.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_nested(f, &self.to_value(), &mut Vec::new())
+    }
+}
+
+/// A hash map that iterates in insertion order. Assigning to an existing key
+/// keeps its original position.
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Key, Value)>,
+    indices: HashMap<Key, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.indices.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.entries.iter().map(|(key, _)| key)
+    }
 }
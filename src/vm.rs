@@ -0,0 +1,498 @@
+use std::rc::Rc;
+
+use crate::builtins;
+use crate::bytecode::{Code, Instruction, Pattern, Program};
+use crate::error::{ErrorKind, SaltError};
+use crate::ops::{self, IndexSpans, RangeIter};
+use crate::value::{Callable, Closure, EnumValue, Instance, Key, Map, Value};
+
+/// A function call in progress.
+struct Frame {
+    code: Rc<Code>,
+    /// The next instruction, saved while the frame is calling another.
+    ip: usize,
+    /// Where the local slots of the frame start on the stack.
+    base: usize,
+    /// What the stack is truncated to on return, dropping the callee too.
+    start: usize,
+    /// The number of iterations in progress when the frame was entered.
+    iterations: usize,
+}
+
+/// A `for` loop in progress.
+enum Iteration {
+    Range(RangeIter),
+    Items(std::vec::IntoIter<Value>),
+}
+
+/// Runs a program compiled to bytecode. It computes the same values and
+/// reports the same errors as the tree-walking interpreter.
+pub struct Vm {
+    program: Program,
+    /// A value for each named function, so that they compare equal.
+    functions: Vec<Value>,
+    /// Globals whose initializer has run.
+    globals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    iterations: Vec<Iteration>,
+}
+
+impl Vm {
+    pub fn new(program: Program) -> Self {
+        let functions = program
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, code)| {
+                Value::Function(Rc::new(Closure {
+                    function: Callable::Compiled {
+                        index,
+                        name: code.name.clone(),
+                    },
+                    captured: Vec::new(),
+                }))
+            })
+            .collect();
+        let globals = vec![None; program.globals];
+        Self {
+            program,
+            functions,
+            globals,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            iterations: Vec::new(),
+        }
+    }
+
+    /// Evaluates the globals of every module in order, then runs `main`.
+    pub fn run(&mut self) -> Result<Value, SaltError> {
+        for initializer in self.program.initializers.clone() {
+            self.execute(initializer)?;
+        }
+        let Some(main) = self.program.main else {
+            return Err(SaltError::new(
+                ErrorKind::Runtime,
+                "Could not find function main",
+                None,
+            ));
+        };
+        let main = self.program.functions[main as usize].clone();
+        ops::check_arity(&main.name, main.arity, 0, main.span, main.span)?;
+        self.execute(main)
+    }
+
+    /// Runs `code`, which takes no arguments, to completion.
+    fn execute(&mut self, code: Rc<Code>) -> Result<Value, SaltError> {
+        let start = self.stack.len();
+        self.push_frame(code, &[], start);
+        self.run_frames()
+    }
+
+    /// Enters `code` with its arguments on top of the stack.
+    fn push_frame(&mut self, code: Rc<Code>, captured: &[Value], start: usize) {
+        let base = self.stack.len() - code.arity;
+        self.stack.resize(base + code.slots, Value::Unit);
+        for (capture, value) in code.captures.iter().zip(captured) {
+            self.stack[base + capture.to as usize] = value.clone();
+        }
+        self.frames.push(Frame {
+            code,
+            ip: 0,
+            base,
+            start,
+            iterations: self.iterations.len(),
+        });
+    }
+
+    /// Calls `callee` with the `arguments` on top of the stack, above the
+    /// callee itself if `start` is below them. Returns whether a frame was
+    /// entered; builtins push their result right away.
+    fn call(
+        &mut self,
+        callee: &Value,
+        arguments: usize,
+        start: usize,
+        code: &Code,
+        span: u32,
+        callee_span: u32,
+    ) -> Result<bool, SaltError> {
+        let span = code.spans[span as usize];
+        match callee {
+            Value::Function(closure) => {
+                let Callable::Compiled { index, .. } = closure.function else {
+                    unreachable!("the VM only creates compiled functions");
+                };
+                let callee = self.program.functions[index].clone();
+                ops::check_arity(&callee.name, callee.arity, arguments, span, callee.span)?;
+                self.push_frame(callee, &closure.captured, start);
+                Ok(true)
+            }
+            Value::Builtin(name) => {
+                let arguments = self.stack.split_off(self.stack.len() - arguments);
+                self.stack.truncate(start);
+                let result = builtins::call(name, arguments, span)
+                    .expect("builtin values name existing builtins")?;
+                self.stack.push(result);
+                Ok(false)
+            }
+            other => Err(ops::not_callable(other, code.spans[callee_span as usize])),
+        }
+    }
+
+    /// Leaves the current frame for a tail call, moving the `arguments` on
+    /// top of the stack down to where the frame started. Returns that
+    /// start, for the frame of the callee.
+    fn replace_frame(&mut self, arguments: usize) -> usize {
+        let frame = self.frames.pop().expect("a frame is running");
+        let first = self.stack.len() - arguments;
+        self.stack.drain(frame.start..first);
+        self.iterations.truncate(frame.iterations);
+        frame.start
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler balances the stack")
+    }
+
+    /// Pops a range bound checked by `RangeBound` or `RangeStep`.
+    fn pop_integer(&mut self) -> i64 {
+        match self.pop() {
+            Value::Integer(i) => i,
+            other => unreachable!("range bound {other} was not checked"),
+        }
+    }
+
+    /// Executes instructions until the frame on top when it is called
+    /// returns.
+    fn run_frames(&mut self) -> Result<Value, SaltError> {
+        let depth = self.frames.len() - 1;
+        let frame = self.frames.last().expect("a frame was pushed");
+        let (mut code, mut ip, mut base) = (frame.code.clone(), frame.ip, frame.base);
+        loop {
+            let instruction = code.instructions[ip];
+            ip += 1;
+            match instruction {
+                Instruction::Constant(constant) => {
+                    self.stack.push(code.constants[constant as usize].clone());
+                }
+                Instruction::Unit => self.stack.push(Value::Unit),
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::GetLocal(slot) => {
+                    let value = self.stack[base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Instruction::SetLocal(slot) => {
+                    self.stack[base + slot as usize] = self.pop();
+                }
+                Instruction::GetGlobal { global, error } => match &self.globals[global as usize] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => return Err(code.errors[error as usize].clone()),
+                },
+                Instruction::SetGlobal(global) => {
+                    self.globals[global as usize] = Some(self.pop());
+                }
+                Instruction::Function(function) => {
+                    self.stack.push(self.functions[function as usize].clone());
+                }
+                Instruction::Closure(function) => {
+                    let lambda = self.program.functions[function as usize].clone();
+                    let captured = lambda
+                        .captures
+                        .iter()
+                        .map(|capture| self.stack[base + capture.from as usize].clone())
+                        .collect();
+                    let closure = Closure {
+                        function: Callable::Compiled {
+                            index: function as usize,
+                            name: lambda.name.clone(),
+                        },
+                        captured,
+                    };
+                    self.stack.push(Value::Function(Rc::new(closure)));
+                }
+                Instruction::Raise(error) => return Err(code.errors[error as usize].clone()),
+                Instruction::Unary { op, span } => {
+                    let value = self.pop();
+                    let result = ops::unary(op, value, code.spans[span as usize])?;
+                    self.stack.push(result);
+                }
+                Instruction::Binary { op, span } => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = ops::binary(op, left, right, code.spans[span as usize])?;
+                    self.stack.push(result);
+                }
+                Instruction::ShortCircuit { op, target, span } => {
+                    let value = self.pop();
+                    let left = ops::logical_operand(op, value, code.spans[span as usize])?;
+                    let short_circuit = op == crate::ast::BinaryOpType::Or;
+                    if left == short_circuit {
+                        self.stack.push(Value::Boolean(left));
+                        ip = target as usize;
+                    }
+                }
+                Instruction::LogicalOperand { op, span } => {
+                    let value = self.pop();
+                    let right = ops::logical_operand(op, value, code.spans[span as usize])?;
+                    self.stack.push(Value::Boolean(right));
+                }
+                Instruction::Jump(target) => ip = target as usize,
+                Instruction::JumpUnless { target, span } => {
+                    let value = self.pop();
+                    if !ops::condition(value, code.spans[span as usize])? {
+                        ip = target as usize;
+                    }
+                }
+                Instruction::Call {
+                    arguments,
+                    span,
+                    callee_span,
+                    tail,
+                } => {
+                    let start = self.stack.len() - arguments as usize - 1;
+                    let callee = self.stack[start].clone();
+                    let start = if tail && matches!(callee, Value::Function(_)) {
+                        self.replace_frame(arguments as usize)
+                    } else {
+                        self.frames.last_mut().expect("a frame is running").ip = ip;
+                        start
+                    };
+                    if self.call(&callee, arguments as usize, start, &code, span, callee_span)? {
+                        let frame = self.frames.last().expect("a frame was pushed");
+                        (code, ip, base) = (frame.code.clone(), 0, frame.base);
+                    }
+                }
+                Instruction::CallFunction {
+                    function,
+                    arguments,
+                    span,
+                    tail,
+                } => {
+                    let callee = self.program.functions[function as usize].clone();
+                    let given = arguments as usize;
+                    let span = code.spans[span as usize];
+                    ops::check_arity(&callee.name, callee.arity, given, span, callee.span)?;
+                    let start = if tail {
+                        self.replace_frame(given)
+                    } else {
+                        self.frames.last_mut().expect("a frame is running").ip = ip;
+                        self.stack.len() - given
+                    };
+                    self.push_frame(callee, &[], start);
+                    let frame = self.frames.last().expect("a frame was pushed");
+                    (code, ip, base) = (frame.code.clone(), 0, frame.base);
+                }
+                Instruction::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("a frame is running");
+                    self.stack.truncate(frame.start);
+                    self.iterations.truncate(frame.iterations);
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                    let frame = self.frames.last().expect("the caller is running");
+                    (code, ip, base) = (frame.code.clone(), frame.ip, frame.base);
+                }
+                Instruction::Print => {
+                    let value = self.pop();
+                    println!("{value}");
+                }
+                Instruction::Time => self.stack.push(ops::time()),
+                Instruction::Array(elements) => {
+                    let elements = self.stack.split_off(self.stack.len() - elements as usize);
+                    self.stack.push(Value::array(elements));
+                }
+                Instruction::Map => self.stack.push(Value::map(Map::new())),
+                Instruction::CheckKey { span } => {
+                    let key = self.stack.last().expect("a key was pushed");
+                    Key::from_value(key, code.spans[span as usize])?;
+                }
+                Instruction::InsertEntry => {
+                    let value = self.pop();
+                    let key = self.pop();
+                    let Some(Value::Map(map)) = self.stack.last() else {
+                        unreachable!("entries are inserted into a map literal");
+                    };
+                    let key = Key::from_value(&key, code.span).expect("checked by CheckKey");
+                    map.borrow_mut().insert(key, value);
+                }
+                Instruction::Struct(layout) => {
+                    let layout = &code.structs[layout as usize];
+                    let values = self.stack.split_off(self.stack.len() - layout.fields.len());
+                    let fields = layout
+                        .fields
+                        .iter()
+                        .map(|(name, position)| (name.clone(), values[*position].clone()))
+                        .collect();
+                    let instance = Instance::new(layout.name.clone(), layout.module, fields);
+                    self.stack.push(Value::Struct(Rc::new(instance)));
+                }
+                Instruction::Enum { variant, fields } => {
+                    let (module, enum_name, variant) = &code.variants[variant as usize];
+                    let fields = self.stack.split_off(self.stack.len() - fields as usize);
+                    self.stack.push(Value::Enum(Rc::new(EnumValue {
+                        enum_name: enum_name.clone(),
+                        module: *module,
+                        variant: variant.clone(),
+                        fields,
+                    })));
+                }
+                Instruction::Index {
+                    span,
+                    target_span,
+                    index_span,
+                } => {
+                    let position = self.pop();
+                    let target = self.pop();
+                    let spans = index_spans(&code, span, target_span, index_span);
+                    self.stack.push(ops::index(target, &position, &spans)?);
+                }
+                Instruction::SetIndex {
+                    span,
+                    target_span,
+                    index_span,
+                } => {
+                    let value = self.pop();
+                    let position = self.pop();
+                    let target = self.pop();
+                    let spans = index_spans(&code, span, target_span, index_span);
+                    ops::set_index(target, &position, value, &spans)?;
+                }
+                Instruction::GetField {
+                    name,
+                    span,
+                    target_span,
+                } => {
+                    let name = &code.names[name as usize];
+                    let target = self.pop();
+                    let instance =
+                        ops::expect_instance(target, name, code.spans[target_span as usize])?;
+                    match instance.get(name) {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(self.unknown_field(&code, &instance, name, span)),
+                    }
+                }
+                Instruction::SetField {
+                    name,
+                    span,
+                    target_span,
+                } => {
+                    let name = &code.names[name as usize];
+                    let value = self.pop();
+                    let target = self.pop();
+                    let instance =
+                        ops::expect_instance(target, name, code.spans[target_span as usize])?;
+                    if !instance.set(name, value) {
+                        return Err(self.unknown_field(&code, &instance, name, span));
+                    }
+                }
+                Instruction::RangeBound { span } => {
+                    let value = self.pop();
+                    let bound = ops::range_bound(value, code.spans[span as usize])?;
+                    self.stack.push(Value::Integer(bound));
+                }
+                Instruction::RangeStep { span } => {
+                    let value = self.pop();
+                    let step = ops::range_step(value, code.spans[span as usize])?;
+                    self.stack.push(Value::Integer(step));
+                }
+                Instruction::IterRange { inclusive } => {
+                    let step = self.pop_integer();
+                    let end = self.pop_integer();
+                    let start = self.pop_integer();
+                    let range = RangeIter::new(start, end, step, inclusive);
+                    self.iterations.push(Iteration::Range(range));
+                }
+                Instruction::IterItems { span } => {
+                    let value = self.pop();
+                    let items = ops::iteration_items(value, code.spans[span as usize])?;
+                    self.iterations.push(Iteration::Items(items.into_iter()));
+                }
+                Instruction::IterNext { exit } => {
+                    let next = match self.iterations.last_mut() {
+                        Some(Iteration::Range(range)) => range.next().map(Value::Integer),
+                        Some(Iteration::Items(items)) => items.next(),
+                        None => unreachable!("IterNext outside a loop"),
+                    };
+                    match next {
+                        Some(item) => self.stack.push(item),
+                        None => ip = exit as usize,
+                    }
+                }
+                Instruction::IterEnd => {
+                    self.iterations.pop();
+                }
+                Instruction::Match { pattern, otherwise } => {
+                    let value = self.pop();
+                    let slots = &mut self.stack[base..base + code.slots];
+                    if !match_pattern(&code.patterns[pattern as usize], &value, slots) {
+                        ip = otherwise as usize;
+                    }
+                }
+                Instruction::NoMatch { span } => {
+                    let scrutinee = self.pop();
+                    let span = code.spans[span as usize];
+                    return Err(ops::no_match(&scrutinee, code.module, span));
+                }
+            }
+        }
+    }
+
+    fn unknown_field(&self, code: &Code, instance: &Instance, field: &str, span: u32) -> SaltError {
+        let definition = self
+            .program
+            .structs
+            .get(&(instance.module, instance.name.clone()));
+        ops::unknown_field(
+            &instance.name,
+            field,
+            code.spans[span as usize],
+            definition.copied(),
+        )
+    }
+}
+
+fn index_spans(code: &Code, span: u32, target_span: u32, index_span: u32) -> IndexSpans {
+    IndexSpans {
+        whole: code.spans[span as usize],
+        target: code.spans[target_span as usize],
+        index: code.spans[index_span as usize],
+    }
+}
+
+/// Matches `value` against `pattern`, storing its bindings in `slots`. A
+/// failed match may leave some bindings stored, which the compiled code
+/// never reads.
+fn match_pattern(pattern: &Pattern, value: &Value, slots: &mut [Value]) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Binding(slot), value) => {
+            slots[*slot as usize] = value.clone();
+            true
+        }
+        (Pattern::Literal(literal), value) => ops::equal(literal, value),
+        (
+            Pattern::Variant {
+                module,
+                enum_name,
+                variant,
+                fields,
+            },
+            Value::Enum(value),
+        ) => {
+            *enum_name == value.enum_name
+                && *module == value.module
+                && *variant == value.variant
+                && fields.len() == value.fields.len()
+                && fields
+                    .iter()
+                    .zip(&value.fields)
+                    .all(|(pattern, value)| match_pattern(pattern, value, slots))
+        }
+        (Pattern::Variant { .. }, _) => false,
+    }
+}